@@ -0,0 +1,26 @@
+// Builds a 100k element list by repeated concatenation and indexes every element of it.
+// Run with `cargo bench --bench concat`.
+use labra_minus::sync::Rc;
+use std::time::Instant;
+
+use labra_minus::parsetree::ParseTree;
+use labra_minus::value::{ConcatList, EncapsulateList, ExactList, ListLike, Value};
+
+const ELEMENTS: i64 = 100_000;
+
+fn main() {
+    let start = Instant::now();
+    let mut list: Rc<dyn ListLike> = Rc::new(ExactList::new(Vec::new()));
+    for n in 0..ELEMENTS {
+        let element = EncapsulateList::new(ParseTree::Number{n, line: 0, col: 0}, Value::Number(0));
+        list = ConcatList::concat(list, Rc::new(element));
+    }
+    let mut sum = 0;
+    for i in 0..ELEMENTS {
+        if let Ok(Value::Number(n)) = list.index(i) {
+            sum += n;
+        }
+    }
+    assert_eq!(sum, ELEMENTS * (ELEMENTS - 1) / 2);
+    println!("concat {} elements and index each one: {:?}", ELEMENTS, start.elapsed());
+}
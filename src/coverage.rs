@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use super::parsetree::{NodeId, ParseTree, Parser};
+use super::profile::{self, Profile};
+
+// Which nodes of programs have been evaluated over any number of runs, for `--coverage`.
+// It is kept as an lcov tracefile with a branch for every node, numbered by its column, on
+// the line the node starts on. That way later runs can add to it, and lcov tools can read it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Tracefile {
+    // the evaluations of every node, by the path of the program it is in
+    pub programs: BTreeMap<String, BTreeMap<NodeId, u64>>,
+}
+
+impl Tracefile {
+    // Adds the evaluations from one run of a program. Nodes that weren't evaluated are
+    // recorded too, so that they show up as never evaluated.
+    pub fn record(&mut self, path: &str, expression: &ParseTree, profile: &Profile) {
+        let counts = self.programs.entry(path.to_owned()).or_default();
+        for id in profile::index(expression).into_keys() {
+            let evaluations = profile.nodes.get(&id).map_or(0, |n| n.evaluations);
+            *counts.entry(id).or_insert(0) += evaluations;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseTracefileError(pub String);
+
+impl FromStr for Tracefile {
+    type Err = ParseTracefileError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tracefile = Tracefile::default();
+        let mut program = None;
+        for (i, l) in s.lines().enumerate() {
+            let l = l.trim();
+            if let Some(path) = l.strip_prefix("SF:") {
+                program = Some(path.to_owned());
+            } else if l == "end_of_record" {
+                program = None;
+            } else if let Some(branch) = l.strip_prefix("BRDA:") {
+                let error = || ParseTracefileError(format!("malformed branch on line {}: {}", i + 1, l));
+                let fields: Vec<&str> = branch.split(',').collect();
+                let (line, col, count) = match fields[..] {
+                    [line, _, col, count] => (
+                        line.parse().map_err(|_| error())?,
+                        col.parse().map_err(|_| error())?,
+                        // lcov writes - for branches that were never reached
+                        if count == "-" { 0 } else { count.parse().map_err(|_| error())? },
+                    ),
+                    _ => return Err(error()),
+                };
+                let path = program.clone().ok_or_else(error)?;
+                *tracefile.programs.entry(path).or_default().entry((line, col)).or_insert(0) += count;
+            }
+            // everything else is recomputed from the branches
+        }
+        Ok(tracefile)
+    }
+}
+
+impl fmt::Display for Tracefile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (path, counts) in &self.programs {
+            writeln!(f, "TN:")?;
+            writeln!(f, "SF:{}", path)?;
+            let mut lines: BTreeMap<u32, u64> = BTreeMap::new();
+            for ((line, col), count) in counts {
+                match count {
+                    0 => writeln!(f, "BRDA:{},0,{},-", line, col)?,
+                    _ => writeln!(f, "BRDA:{},0,{},{}", line, col, count)?,
+                }
+                *lines.entry(*line).or_insert(0) += count;
+            }
+            writeln!(f, "BRF:{}", counts.len())?;
+            writeln!(f, "BRH:{}", counts.values().filter(|c| **c > 0).count())?;
+            for (line, count) in &lines {
+                writeln!(f, "DA:{},{}", line, count)?;
+            }
+            writeln!(f, "LF:{}", lines.len())?;
+            writeln!(f, "LH:{}", lines.values().filter(|c| **c > 0).count())?;
+            writeln!(f, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+// The source with the evaluations of the nodes on each line in front of it, or ##### if
+// none of them were evaluated. Lines with nodes that were never evaluated are followed by
+// a line marking both brackets of each of those nodes (or its first digit, or its !).
+pub fn annotate(source: &str, counts: &BTreeMap<NodeId, u64>) -> String {
+    let mut parser = Parser::new(source);
+    // programs that don't parse have no counts to show anyway
+    let _ = parser.parse();
+    let closers = parser.closers();
+
+    let mut lines: HashMap<u32, u64> = HashMap::new();
+    let mut marks: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (id, count) in counts {
+        *lines.entry(id.0).or_insert(0) += count;
+        if *count == 0 {
+            marks.entry(id.0).or_default().push(id.1);
+            if let Some((line, col)) = closers.get(id) {
+                marks.entry(*line).or_default().push(*col);
+            }
+        }
+    }
+
+    let mut annotated = String::new();
+    for (i, text) in source.lines().enumerate() {
+        let linenum = i as u32 + 1;
+        match lines.get(&linenum) {
+            Some(0) => annotated.push_str(&format!("{:>9} | {}\n", "#####", text)),
+            Some(count) => annotated.push_str(&format!("{:>9} | {}\n", count, text)),
+            None => annotated.push_str(&format!("{:>9} | {}\n", "", text)),
+        }
+        if let Some(cols) = marks.get(&linenum) {
+            let width = cols.iter().max().copied().unwrap_or(0);
+            let marker: String = (1..=width).map(|c| if cols.contains(&c) { '^' } else { ' ' }).collect();
+            annotated.push_str(&format!("{:>9} | {}\n", "", marker));
+        }
+    }
+    let evaluated = counts.values().filter(|c| **c > 0).count();
+    annotated.push_str(&format!("{}/{} nodes evaluated\n", evaluated, counts.len()));
+    annotated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::evaluate::evaluate;
+    use crate::observer::{self, Observer};
+    use crate::parsetree::parse;
+    use crate::profile::Profiler;
+    use crate::value::{inductionlist, Value};
+
+    // runs source the way --coverage does
+    fn run(tracefile: &mut Tracefile, source: &str, input: i64) {
+        let pt = parse(source).expect("parse error");
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        inductionlist::set_closed_forms(false);
+        let _ = observer::observe(&[profiler.clone() as Observer], || {
+            evaluate(&pt, &Value::Number(input)).and_then(|v| v.force_resolve())
+        });
+        inductionlist::set_closed_forms(true);
+        let profile = profiler.borrow_mut().finish(&pt);
+        tracefile.record("program.txt", &pt, &profile);
+    }
+
+    #[test]
+    fn unevaluated_nodes() {
+        // the element of 5[] is never needed
+        let mut tracefile = Tracefile::default();
+        run(&mut tracefile, "1[](5[])[0]", 0);
+        let counts = &tracefile.programs["program.txt"];
+        assert_eq!(counts[&(1, 1)], 1);
+        assert_eq!(counts[&(1, 5)], 0);
+        assert_eq!(counts[&(1, 6)], 1);
+        assert_eq!(
+            annotate("1[](5[])[0]", counts),
+            "        6 | 1[](5[])[0]\n          |     ^\n6/7 nodes evaluated\n"
+        );
+
+        // a map over an empty list never runs its body, so every bracket of it is marked
+        let mut tracefile = Tracefile::default();
+        run(&mut tracefile, "[][()(1))", 0);
+        assert_eq!(
+            annotate("[][()(1))", &tracefile.programs["program.txt"]),
+            "        2 | [][()(1))\n          |    ^^^^^\n2/5 nodes evaluated\n"
+        );
+    }
+
+    #[test]
+    fn closed_form_inductions() {
+        // 0(()(1)] would otherwise be a ProgressionList, which never runs ()(1)
+        let mut tracefile = Tracefile::default();
+        run(&mut tracefile, "0(()(1)][3]", 0);
+        let counts = &tracefile.programs["program.txt"];
+        assert_eq!(counts[&(1, 5)], 3);
+        assert!(counts.values().all(|c| *c > 0));
+    }
+
+    #[test]
+    fn runs_add_up() {
+        // the body of the encapsulation only runs for lists
+        let source = "()[]\n[0]\n(1)";
+        let mut tracefile = Tracefile::default();
+        run(&mut tracefile, source, 0);
+        assert_eq!(tracefile.programs["program.txt"][&(2, 1)], 1);
+        let first = tracefile.clone();
+        run(&mut tracefile, source, 5);
+        assert_eq!(tracefile.programs["program.txt"][&(2, 1)], 2);
+        assert_eq!(tracefile.programs["program.txt"][&(1, 1)], 2);
+
+        // the lcov file reads back as the same counts
+        assert_eq!(first.to_string().parse::<Tracefile>(), Ok(first));
+        assert_eq!(tracefile.to_string().parse::<Tracefile>(), Ok(tracefile));
+    }
+
+    #[test]
+    fn lcov_format() {
+        let mut tracefile = Tracefile::default();
+        run(&mut tracefile, "1[](5[])\n[0]", 0);
+        assert_eq!(tracefile.to_string(), "\
+TN:
+SF:program.txt
+BRDA:1,0,1,1
+BRDA:1,0,2,1
+BRDA:1,0,4,1
+BRDA:1,0,5,-
+BRDA:1,0,6,1
+BRDA:2,0,1,1
+BRDA:2,0,2,1
+BRF:7
+BRH:6
+DA:1,4
+DA:2,2
+LF:2
+LH:2
+end_of_record
+");
+        assert!("SF:a\nBRDA:1,0,x,1\n".parse::<Tracefile>().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use super::parsetree::ParseTree;
+use super::stats;
+use super::sync::Rc;
+
+// Common subexpression sharing. Within one scope (the whole program, or a single function
+// body) every subtree sees the same input, so structurally identical subtrees always have
+// the same value. Repeated ones are wrapped in ParseTree::Shared, and the backends evaluate
+// each slot at most once per scope, like ()[0] in ()[0](()[0]).
+// Function bodies are evaluated separately for every input, so each one is its own scope
+// and nothing is shared across them. Subtrees with debug operators are never shared, since
+// that would change how often they print.
+pub fn share(expression: &Rc<ParseTree>) -> Rc<ParseTree> {
+    let mut ids = Ids::default();
+    ids.find(expression);
+    share_scope(expression, &ids)
+}
+
+fn share_scope(expression: &Rc<ParseTree>, ids: &Ids) -> Rc<ParseTree> {
+    let mut counts = HashMap::new();
+    let mut order = Vec::new();
+    count(expression, ids, &mut counts, &mut order);
+
+    // slots are numbered in the order the subtrees first appear
+    let mut slots = HashMap::new();
+    for id in order {
+        if counts[&id] >= 2 {
+            slots.insert(id, slots.len());
+            stats::count_shared_subexpression();
+        }
+    }
+    rewrite(expression, ids, &slots)
+}
+
+// Numbers every subtree by its code, so that identical subtrees get the same id, along with
+// whether it has a debug operator. Each node is only looked at once, so comparing subtrees
+// never means printing or walking them again.
+#[derive(Default)]
+struct Ids {
+    nodes: HashMap<*const ParseTree, (usize, bool)>,
+    // a node's form, its number if it is one and the ids of its children
+    codes: HashMap<(&'static str, i64, usize, usize), usize>,
+}
+
+impl Ids {
+    fn find(&mut self, expression: &ParseTree) -> (usize, bool) {
+        let node = expression as *const ParseTree;
+        if let Some(&found) = self.nodes.get(&node) {
+            return found;
+        }
+        let (code, debug) = match expression {
+            ParseTree::Number{n, ..} => ((expression.form(), *n, 0, 0), false),
+            ParseTree::Input{..} | ParseTree::EmptyList{..} => ((expression.form(), 0, 0, 0), false),
+            ParseTree::Shared{arg, ..} => {
+                let found = self.find(arg);
+                self.nodes.insert(node, found);
+                return found;
+            }
+            // X[] has the same code whether or not it is strict
+            ParseTree::Length{arg, ..} | ParseTree::Debug{arg, ..} |
+            ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} => {
+                let (id, debug) = self.find(arg);
+                ((expression.form(), 0, id, 0), debug || matches!(expression, ParseTree::Debug{..}))
+            }
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => {
+                let ((id1, debug1), (id2, debug2)) = (self.find(arg1), self.find(arg2));
+                ((expression.form(), 0, id1, id2), debug1 || debug2)
+            }
+        };
+        let next = self.codes.len();
+        let found = (*self.codes.entry(code).or_insert(next), debug);
+        self.nodes.insert(node, found);
+        found
+    }
+
+    fn get(&self, expression: &ParseTree) -> (usize, bool) {
+        self.nodes[&(expression as *const ParseTree)]
+    }
+}
+
+// whether a subtree is worth sharing at all
+fn shareable(expression: &ParseTree, ids: &Ids) -> bool {
+    !matches!(
+        expression,
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} | ParseTree::Shared{..}
+    ) && !ids.get(expression).1
+}
+
+// the children that are evaluated in the same scope as their parent
+fn in_scope(expression: &ParseTree) -> Vec<&Rc<ParseTree>> {
+    match expression {
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} | ParseTree::Encapsulate{..} => vec![],
+        ParseTree::Length{arg, ..} | ParseTree::Debug{arg, ..} |
+        ParseTree::Shared{arg, ..} | ParseTree::Strict{arg, ..} => vec![arg],
+        ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} => vec![arg1, arg2],
+        ParseTree::Induction{arg1, ..} | ParseTree::Map{arg1, ..} => vec![arg1],
+    }
+}
+
+// Counts the subtrees of a scope by their code. Repeats aren't looked into, since their
+// children will never be evaluated.
+fn count(expression: &ParseTree, ids: &Ids, counts: &mut HashMap<usize, usize>, order: &mut Vec<usize>) {
+    if shareable(expression, ids) {
+        let id = ids.get(expression).0;
+        let n = counts.entry(id).or_insert(0);
+        *n += 1;
+        if *n > 1 {
+            return;
+        }
+        order.push(id);
+    }
+    for child in in_scope(expression) {
+        count(child, ids, counts, order);
+    }
+}
+
+fn rewrite(expression: &Rc<ParseTree>, ids: &Ids, slots: &HashMap<usize, usize>) -> Rc<ParseTree> {
+    let same = |arg: &Rc<ParseTree>| rewrite(arg, ids, slots);
+    let body = |arg: &Rc<ParseTree>| share_scope(arg, ids);
+    let node = match &**expression {
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} | ParseTree::Shared{..} =>
+            return expression.clone(),
+        ParseTree::Length{arg, line, col} => ParseTree::Length{arg: same(arg), line: *line, col: *col},
+        ParseTree::Debug{arg, line, col} => ParseTree::Debug{arg: same(arg), line: *line, col: *col},
+        ParseTree::Encapsulate{arg, line, col} => ParseTree::Encapsulate{arg: body(arg), line: *line, col: *col},
+        ParseTree::Strict{arg, line, col} => ParseTree::Strict{arg: same(arg), line: *line, col: *col},
+        ParseTree::Addition{arg1, arg2, line, col} =>
+            ParseTree::Addition{arg1: same(arg1), arg2: same(arg2), line: *line, col: *col},
+        ParseTree::IndexSubtraction{arg1, arg2, line, col} =>
+            ParseTree::IndexSubtraction{arg1: same(arg1), arg2: same(arg2), line: *line, col: *col},
+        ParseTree::Induction{arg1, arg2, line, col} =>
+            ParseTree::Induction{arg1: same(arg1), arg2: body(arg2), line: *line, col: *col},
+        ParseTree::Map{arg1, arg2, line, col} =>
+            ParseTree::Map{arg1: same(arg1), arg2: body(arg2), line: *line, col: *col},
+    };
+    let node = Rc::new(node);
+    match slots.get(&ids.get(expression).0) {
+        Some(slot) if shareable(expression, ids) => Rc::new(ParseTree::Shared{arg: node, slot: *slot}),
+        _ => node,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+    use crate::value::Value;
+
+    fn shared(expr: &str) -> Rc<ParseTree> {
+        share(&Rc::new(parse(expr).expect("parse error")))
+    }
+
+    // the slots in a scope, in the order they are evaluated
+    fn slots(expression: &ParseTree) -> Vec<(String, usize)> {
+        let mut found = Vec::new();
+        if let ParseTree::Shared{arg, slot} = expression {
+            found.push((arg.to_string(), *slot));
+        }
+        for child in in_scope(expression) {
+            found.extend(slots(child));
+        }
+        found
+    }
+
+    #[test]
+    fn repeated_subtrees() {
+        let pt = shared("()[0](1)(()[0](1))(()[0])");
+        assert_eq!(pt.to_string(), "()[0](1)(()[0](1))(()[0])");
+        assert_eq!(slots(&pt), vec![
+            (String::from("()[0](1)"), 0),
+            (String::from("()[0]"), 1),
+            (String::from("()[0](1)"), 0),
+            (String::from("()[0]"), 1),
+            (String::from("()[0]"), 1),
+        ]);
+    }
+
+    #[test]
+    fn scopes() {
+        // the ()[0] in the map body sees a different input than the ones outside it
+        let pt = shared("()[0][][()[0](()[0]))(()[0][])");
+        assert_eq!(slots(&pt), vec![(String::from("()[0][]"), 0), (String::from("()[0][]"), 0)]);
+        match &*pt {
+            ParseTree::Addition{arg1, ..} => match &**arg1 {
+                ParseTree::Map{arg2, ..} => assert_eq!(slots(arg2).len(), 2),
+                _ => panic!("expected a map"),
+            },
+            _ => panic!("expected an addition"),
+        }
+    }
+
+    #[test]
+    fn debugs_are_not_shared() {
+        // both debugs still print, but what they print is only computed once
+        assert_eq!(slots(&shared("()[0]!(()[0]!)")), vec![(String::from("()[0]"), 0), (String::from("()[0]"), 0)]);
+        assert_eq!(slots(&shared("1[0](1[0])")).len(), 2);
+    }
+
+    #[test]
+    fn shared_values() {
+        stats::reset();
+        let pt = shared("()[0](()[1])(()[0](()[1]))");
+        let input = "[3, 4]".parse::<crate::value::ExactList>().expect("bad list");
+        let result = evaluate(&pt, &Value::List(Rc::new(input))).expect("evaluation failure");
+        assert!(matches!(result, Value::Number(14)));
+        assert_eq!(stats::get().shared_subexpressions, 1);
+        assert_eq!(stats::get().shared_reuses, 1);
+    }
+
+    // every example should behave the same with and without sharing, on both backends
+    #[test]
+    fn shared_examples() {
+        crate::differential::behaves_the_same(&[], share, true);
+    }
+}
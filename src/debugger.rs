@@ -0,0 +1,360 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use super::errors::RuntimeError;
+use super::observer::EvalObserver;
+use super::parsetree::{NodeId, ParseTree};
+use super::profile;
+use super::sync::Rc;
+use super::value::{Function, Value};
+
+// A step debugger for the tree walker, see `labra-minus debug`. As an observer it sees every
+// node evaluated and every element of a lazy list computed, so it can stop at breakpoints
+// and follow evaluation in and out of function bodies. Anything it evaluates while stopped
+// runs without observers, so it doesn't stop inside of itself.
+const HELP: &str = "\
+commands:
+  s, step             stop at the next node, going into function bodies
+  n, next             stop at the next node, without going into function bodies
+  o, out              stop at the next node after leaving the current body
+  c, continue         run until a breakpoint
+  b, break LINE:COL   stop whenever the node at LINE:COL is evaluated
+  d, delete LINE:COL  remove a breakpoint
+  p, print            show the current node, its input () and the forced elements
+  bt, chain           show the nodes being evaluated and the elements they forced
+  i, inspect [I ...]  show (), or its element at I, then that element's element at the next index...
+  q, quit             stop the program
+  h, help             show this
+an empty line repeats the last command
+";
+
+enum Mode {
+    Step,
+    // stop at a node with at most this many forced elements on the stack
+    Next(usize),
+    Out(usize),
+    Continue,
+}
+
+enum Frame {
+    Node{id: NodeId, input: Value},
+    // an element of a lazy list being computed
+    Element{kind: &'static str, index: i64, body: String},
+}
+
+pub struct Debugger {
+    program: Rc<ParseTree>,
+    commands: Box<dyn BufRead>,
+    out: Box<dyn Write>,
+    breakpoints: HashSet<NodeId>,
+    mode: Mode,
+    stack: Vec<Frame>,
+    last_command: String,
+}
+
+impl Debugger {
+    // A debugger for program that stops at its first node, reading commands from commands.
+    pub fn new(program: Rc<ParseTree>, commands: Box<dyn BufRead>, out: Box<dyn Write>) -> Debugger {
+        Debugger {
+            program,
+            commands,
+            out,
+            breakpoints: HashSet::new(),
+            mode: Mode::Step,
+            stack: Vec::new(),
+            last_command: String::new(),
+        }
+    }
+}
+
+impl EvalObserver for Debugger {
+    fn on_enter_node(&mut self, node: &ParseTree, input: &Value) {
+        self.stack.push(Frame::Node{id: node.id(), input: input.clone()});
+        let depth = self.depth();
+        let breakpoint = self.breakpoints.contains(&node.id());
+        let stop = breakpoint || match self.mode {
+            Mode::Step => true,
+            Mode::Next(max) => depth <= max,
+            Mode::Out(max) => depth < max,
+            Mode::Continue => false,
+        };
+        if stop {
+            self.stop(breakpoint);
+        }
+    }
+
+    fn on_exit_node(&mut self, _node: &ParseTree, _value: &Value) {
+        self.stack.pop();
+    }
+
+    fn on_error(&mut self, _node: &ParseTree, _error: &RuntimeError) {
+        self.stack.pop();
+    }
+
+    fn on_element_forcing(&mut self, kind: &'static str, index: i64, f: &Function) {
+        self.stack.push(Frame::Element{kind, index, body: describe(f)});
+    }
+
+    fn on_element_forced(&mut self, _kind: &'static str, _index: i64, _f: &Function, _result: &Result<Value, RuntimeError>) {
+        self.stack.pop();
+    }
+}
+
+fn describe(f: &Function) -> String {
+    match f {
+        Function::Tree(pt) => {
+            let (line, col) = pt.position();
+            format!("{} at {}:{}", pt, line, col)
+        }
+        Function::Code(_, function) => format!("compiled function {}", function),
+        Function::Composed(first, second) => format!("{} then {}", describe(first), describe(second)),
+    }
+}
+
+fn parse_position(s: &str) -> Option<NodeId> {
+    let (line, col) = s.split_once(':')?;
+    Some((line.trim().parse().ok()?, col.trim().parse().ok()?))
+}
+
+impl Debugger {
+    // how many elements are being forced, which is how deep into function bodies evaluation is
+    fn depth(&self) -> usize {
+        self.stack.iter().filter(|f| matches!(f, Frame::Element{..})).count()
+    }
+
+    fn current(&self) -> Option<(NodeId, &Value)> {
+        self.stack.iter().rev().find_map(|f| match f {
+            Frame::Node{id, input} => Some((*id, input)),
+            Frame::Element{..} => None,
+        })
+    }
+
+    fn code(&self, id: NodeId) -> String {
+        match profile::index(&self.program).get(&id) {
+            Some(node) => format!("{} {}", node.form(), node),
+            None => String::new(),
+        }
+    }
+
+    // writing to the terminal can only fail if it has gone away, so errors are ignored
+    fn say(&mut self, text: &str) {
+        let _ = writeln!(self.out, "{}", text);
+    }
+
+    fn stop(&mut self, breakpoint: bool) {
+        if let Some(((line, col), _)) = self.current() {
+            let reason = if breakpoint { "breakpoint" } else { "stopped" };
+            let message = format!("{} at {}:{} {}", reason, line, col, self.code((line, col)));
+            self.say(&message);
+        }
+        loop {
+            let _ = write!(self.out, "(lm) ");
+            let _ = self.out.flush();
+            let mut line = String::new();
+            // without more commands the program runs to the end
+            if self.commands.read_line(&mut line).unwrap_or(0) == 0 {
+                self.mode = Mode::Continue;
+                self.breakpoints.clear();
+                return;
+            }
+            let mut line = line.trim().to_owned();
+            if line.is_empty() {
+                line = self.last_command.clone();
+            }
+            self.last_command = line.clone();
+            if self.command(&line) {
+                return;
+            }
+        }
+    }
+
+    // runs a command, returning whether evaluation should go on
+    fn command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let depth = self.depth();
+        match words.next().unwrap_or("") {
+            "s" | "step" => self.mode = Mode::Step,
+            "n" | "next" => self.mode = Mode::Next(depth),
+            "o" | "out" => self.mode = Mode::Out(depth),
+            "c" | "continue" => self.mode = Mode::Continue,
+            "b" | "break" => {
+                match words.next().and_then(parse_position) {
+                    Some(id) if profile::index(&self.program).contains_key(&id) => {
+                        self.breakpoints.insert(id);
+                        let message = format!("breakpoint at {}:{} {}", id.0, id.1, self.code(id));
+                        self.say(&message);
+                    }
+                    Some((line, col)) => self.say(&format!("no node starts at {}:{}", line, col)),
+                    None => self.say("break needs a position like 3:5"),
+                }
+                return false;
+            }
+            "d" | "delete" => {
+                match words.next().and_then(parse_position) {
+                    Some(id) if self.breakpoints.remove(&id) => self.say(&format!("deleted breakpoint at {}:{}", id.0, id.1)),
+                    Some((line, col)) => self.say(&format!("no breakpoint at {}:{}", line, col)),
+                    None => self.say("delete needs a position like 3:5"),
+                }
+                return false;
+            }
+            "p" | "print" => {
+                self.print();
+                return false;
+            }
+            "bt" | "chain" => {
+                self.chain();
+                return false;
+            }
+            "i" | "inspect" => {
+                let path: Result<Vec<i64>, _> = words.map(|w| w.parse()).collect();
+                match path {
+                    Ok(path) => self.inspect(&path),
+                    Err(_) => self.say("inspect takes the indexes of the element to show"),
+                }
+                return false;
+            }
+            "q" | "quit" => {
+                let _ = self.out.flush();
+                std::process::exit(0);
+            }
+            "h" | "help" => {
+                self.say(HELP.trim_end());
+                return false;
+            }
+            other => {
+                self.say(&format!("unknown command \"{}\", try help", other));
+                return false;
+            }
+        }
+        true
+    }
+
+    fn print(&mut self) {
+        let current = self.current().map(|(id, input)| (id, input.clone()));
+        if let Some(((line, col), input)) = current {
+            let message = format!("node: {}:{} {}\ninput: {:?}", line, col, self.code((line, col)), input);
+            self.say(&message);
+        }
+        self.chain();
+    }
+
+    fn chain(&mut self) {
+        let mut lines = Vec::new();
+        for frame in self.stack.iter().rev() {
+            match frame {
+                Frame::Node{id, ..} => lines.push(format!("  in {}:{} {}", id.0, id.1, self.code(*id))),
+                Frame::Element{kind, index, body} => lines.push(format!("  forcing element {} of {} {}", index, kind, body)),
+            }
+        }
+        for line in lines {
+            self.say(&line);
+        }
+    }
+
+    // shows the input of the current node, or an element inside it, computing it if needed
+    fn inspect(&mut self, path: &[i64]) {
+        let mut value = match self.current() {
+            Some((_, input)) => input.clone(),
+            None => return,
+        };
+        for i in path {
+            value = match value {
+                Value::List(l) => match l.index(*i) {
+                    Ok(v) => v,
+                    Err(e) => return self.say(&format!("error: {}", e)),
+                },
+                Value::Number(_) => return self.say(&format!("{:?} is a number", value)),
+            };
+        }
+        let length = match &value {
+            Value::List(l) => match l.length() {
+                Ok(len) => format!(" (length {})", len),
+                Err(_) => String::from(" (infinite)"),
+            },
+            Value::Number(_) => String::new(),
+        };
+        self.say(&format!("{:?}{}", value, length));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use crate::evaluate::evaluate;
+    use crate::observer::{self, Observer};
+    use crate::parsetree::parse;
+    use crate::value::inductionlist;
+
+    // a writer the test can still read after handing it to the debugger
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn debug(expr: &str, input: Value, commands: &str) -> (Value, String) {
+        let pt = Rc::new(parse(expr).expect("parse error"));
+        let out = Shared::default();
+        let debugger = Debugger::new(pt.clone(), Box::new(Cursor::new(commands.to_owned())), Box::new(out.clone()));
+        let observers: [Observer; 1] = [std::rc::Rc::new(RefCell::new(debugger))];
+        // the way the debug subcommand runs it
+        inductionlist::set_closed_forms(false);
+        let result = observer::observe(&observers, || evaluate(&pt, &input).and_then(|v| {v.force_resolve()?; Ok(v)}));
+        inductionlist::set_closed_forms(true);
+        let printed = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        (result.expect("runtime error"), printed)
+    }
+
+    #[test]
+    fn breakpoints() {
+        let (result, out) = debug("1[](2[])(3[])[()(1))", Value::Number(0), "b 1:17\nc\np\nc\nd 1:17\nc\n");
+        assert_eq!(format!("{:?}", result), "[2, 3, 4]");
+        assert!(out.starts_with("stopped at 1:14 X[Y) 1[](2[])(3[])[()(1))\n"), "{}", out);
+        // stopped once for each of the first two elements
+        assert_eq!(out.matches("breakpoint at 1:17 X(Y) ()(1)").count(), 3, "{}", out);
+        assert!(out.contains("node: 1:17 X(Y) ()(1)\ninput: 1\n  in 1:17 X(Y) ()(1)\n  forcing element 0 of map ()(1) at 1:17\n"), "{}", out);
+
+        // inductions with a closed form still run their bodies
+        let (result, out) = debug("0(()(1)][2]", Value::Number(0), "b 1:5\nc\nd 1:5\nc\n");
+        assert_eq!(format!("{:?}", result), "2");
+        // once when it is set and once when it is hit
+        assert_eq!(out.matches("breakpoint at 1:5 X(Y) ()(1)").count(), 2, "{}", out);
+    }
+
+    #[test]
+    fn stepping() {
+        // stepping into the body of the induction while indexing it, then out of it
+        let (_, out) = debug("1(()(())][2]", Value::Number(0), "s\ns\ns\ns\nbt\nn\no\n");
+        let stops: Vec<_> = out.lines().filter(|l| l.contains("stopped at")).collect();
+        assert_eq!(stops.len(), 6, "{}", out);
+        assert!(stops[3].ends_with("stopped at 1:11 number 2"), "{}", out);
+        assert!(stops[4].ends_with("stopped at 1:5 X(Y) ()(())"), "{}", out);
+        assert!(out.contains("forcing element 1 of induction ()(()) at 1:5"), "{}", out);
+        assert!(stops[5].ends_with("stopped at 1:3 () ()"), "{}", out);
+
+        // stepping over it skips every element
+        let (_, out) = debug("1(()(())][2]", Value::Number(0), "s\ns\ns\nn\n");
+        assert_eq!(out.matches("stopped at").count(), 4, "{}", out);
+    }
+
+    #[test]
+    fn inspecting() {
+        let input = "[[1, 2], 3]".parse::<crate::value::ExactList>().unwrap();
+        let (_, out) = debug("()[0]", Value::List(Rc::new(input)), "i 0 1\ni\ni 5\nbreak 9:9\nc\n");
+        assert!(out.contains("(lm) 2\n"), "{}", out);
+        assert!(out.contains("[[1, 2], 3] (length 2)"), "{}", out);
+        assert!(out.contains("error: "), "{}", out);
+        assert!(out.contains("no node starts at 9:9"), "{}", out);
+    }
+}
@@ -0,0 +1,51 @@
+// Checks that a transform of the parse tree doesn't change what programs do, for every
+// example and a few extra programs, on a handful of inputs.
+use std::fs;
+use std::path::Path;
+
+use super::evaluate::evaluate;
+use super::output;
+use super::parsetree::{parse, ParseTree};
+use super::sync::Rc;
+use super::value::{self, string, Value};
+use super::vm;
+
+// what a run gave, or the error it failed with, followed by what its debugs printed
+fn run(f: impl FnOnce() -> Result<Value, crate::errors::RuntimeError>) -> String {
+    let (result, printed) = output::capture(|| match f().and_then(|v| { v.force_resolve()?; Ok(v) }) {
+        Ok(v) => format!("{}", v),
+        Err(e) => format!("{} {}", e.code(), e),
+    });
+    format!("{}\n{}", result, printed)
+}
+
+// Runs each program as written with the tree walker, then after transform with the tree
+// walker and, if vm is set, compiled for the vm, and checks they all give and print the same.
+pub fn behaves_the_same(programs: &[&str], transform: impl Fn(&Rc<ParseTree>) -> Rc<ParseTree>, vm: bool) {
+    let inputs = [
+        Value::Number(0),
+        Value::Number(7),
+        Value::List(Rc::new(value::ExactList::new(vec![Value::Number(7), Value::Number(2)]))),
+        string::string_to_list("Hello, World!").unwrap_or(Value::Number(0)),
+    ];
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut sources: Vec<(String, String)> = programs.iter().map(|p| (p.to_string(), p.to_string())).collect();
+    for entry in fs::read_dir(dir).expect("could not read examples") {
+        let path = entry.expect("could not read examples").path();
+        let source = fs::read_to_string(&path).expect("could not read example");
+        sources.push((format!("{:?}", path), source));
+    }
+    assert!(sources.len() > programs.len(), "no examples found");
+    for (name, source) in sources {
+        let pt = Rc::new(parse(&source).expect("parse error"));
+        let transformed = transform(&pt);
+        let program = Rc::new(vm::compile(&transformed));
+        for input in &inputs {
+            let expected = run(|| evaluate(&pt, input));
+            assert_eq!(run(|| evaluate(&transformed, input)), expected, "{} with input {}", name, input);
+            if vm {
+                assert_eq!(run(|| vm::run(&program, 0, input)), expected, "{} with input {}", name, input);
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use crate::output::quote;
+use crate::parsetree::{self, NodeId, ParseTree, Span};
+use crate::sync::Rc;
+use crate::value::{Part, Value};
+
+// how many parts of one list are drawn before the rest are left as ...
+const MAX_PARTS: usize = 16;
+// how many lists are drawn before the rest are left out
+const MAX_LISTS: usize = 500;
+
+// Draws a program as a Graphviz graph, for `dot -Tsvg`. Each node is labeled with its form
+// and where it is in the source, and points to its X and Y. Subexpressions shared by
+// cse::share are drawn once, with an arrow from every node that uses them.
+pub fn program(program: &ParseTree, source: &str) -> String {
+    let mut drawing = ProgramDrawing {
+        spans: parsetree::spans(program, source),
+        shared: HashMap::new(),
+        nodes: 0,
+        lines: Vec::new(),
+    };
+    drawing.node(program);
+    graph("program", &drawing.lines)
+}
+
+// Draws a value as a Graphviz graph of the lists it is made of, without computing anything.
+// Lists are drawn once however many lists refer to them, and elements that haven't been
+// computed yet are dashed thunks.
+pub fn value(value: &Value) -> String {
+    let mut drawing = ValueDrawing { lists: HashMap::new(), nodes: 0, lines: Vec::new() };
+    drawing.value(value);
+    graph("value", &drawing.lines)
+}
+
+fn graph(name: &str, lines: &[String]) -> String {
+    let mut graph = format!("digraph {} {{\n    node [shape=box, fontname=\"monospace\"];\n", name);
+    for line in lines {
+        graph.push_str(&format!("    {}\n", line));
+    }
+    graph.push_str("}\n");
+    graph
+}
+
+struct ProgramDrawing {
+    spans: HashMap<NodeId, Span>,
+    // the nodes already drawn for the arguments of ParseTree::Shared
+    shared: HashMap<*const ParseTree, usize>,
+    nodes: usize,
+    lines: Vec<String>,
+}
+
+impl ProgramDrawing {
+    // draws node and everything below it, returning its number
+    fn node(&mut self, node: &ParseTree) -> usize {
+        if let ParseTree::Shared{arg, ..} = node {
+            if let Some(&n) = self.shared.get(&Rc::as_ptr(arg)) {
+                return n;
+            }
+            let n = self.node(arg);
+            self.shared.insert(Rc::as_ptr(arg), n);
+            return n;
+        }
+        let n = self.nodes;
+        self.nodes += 1;
+        let form = match node {
+            ParseTree::Number{n, ..} => n.to_string(),
+            ParseTree::Strict{..} => String::from("X[] (strict)"),
+            _ => String::from(node.form()),
+        };
+        let label = match self.spans.get(&node.id()) {
+            Some((l1, c1, l2, c2)) => format!("{}\n{}:{}-{}:{}", form, l1, c1, l2, c2),
+            None => form,
+        };
+        self.lines.push(format!("n{} [label={}];", n, quote(&label)));
+        let args: Vec<(&str, &ParseTree)> = match node {
+            ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} | ParseTree::Shared{..} => Vec::new(),
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} |
+            ParseTree::Debug{arg, ..} => vec![("X", arg)],
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => vec![("X", arg1), ("Y", arg2)],
+        };
+        for (name, arg) in args {
+            let a = self.node(arg);
+            self.lines.push(format!("n{} -> n{} [label={}];", n, a, quote(name)));
+        }
+        n
+    }
+}
+
+struct ValueDrawing {
+    // the node of every list drawn so far, by address
+    lists: HashMap<*const (), usize>,
+    nodes: usize,
+    lines: Vec<String>,
+}
+
+impl ValueDrawing {
+    fn add(&mut self, attributes: String) -> usize {
+        let n = self.nodes;
+        self.nodes += 1;
+        self.lines.push(format!("v{} [{}];", n, attributes));
+        n
+    }
+
+    // draws value and the lists it refers to, returning its number
+    fn value(&mut self, value: &Value) -> usize {
+        let l = match value {
+            Value::Number(n) => return self.add(format!("shape=ellipse, label={}", quote(&n.to_string()))),
+            Value::List(l) => l,
+        };
+        let address = Rc::as_ptr(l) as *const ();
+        if let Some(&n) = self.lists.get(&address) {
+            return n;
+        }
+        if self.lists.len() == MAX_LISTS {
+            return self.add(String::from("shape=plaintext, label=\"...\""));
+        }
+        let parts = l.parts();
+        let label = if parts.label.is_empty() {
+            String::from(parts.kind)
+        } else {
+            format!("{}\n{}", parts.kind, parts.label)
+        };
+        let n = self.add(format!("label={}", quote(&label)));
+        self.lists.insert(address, n);
+        let hidden = parts.children.len().saturating_sub(MAX_PARTS);
+        for (name, part) in parts.children.into_iter().take(MAX_PARTS) {
+            let child = match part {
+                Part::Value(v) => self.value(&v),
+                Part::Thunk => self.add(String::from("style=dashed, label=\"thunk\"")),
+            };
+            self.lines.push(format!("v{} -> v{} [label={}];", n, child, quote(&name)));
+        }
+        if hidden > 0 {
+            let rest = self.add(format!("shape=plaintext, label={}", quote(&format!("{} more", hidden))));
+            self.lines.push(format!("v{} -> v{};", n, rest));
+        }
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+    use crate::value::ExactList;
+
+    #[test]
+    fn programs() {
+        let source = "1(2)!";
+        let graph = program(&parse(source).expect("parse error"), source);
+        assert!(graph.starts_with("digraph program {\n"));
+        assert!(graph.contains("n0 [label=\"X!\\n1:1-1:5\"];"), "{}", graph);
+        assert!(graph.contains("n1 [label=\"X(Y)\\n1:1-1:4\"];"), "{}", graph);
+        assert!(graph.contains("n3 [label=\"2\\n1:3-1:3\"];"), "{}", graph);
+        assert!(graph.contains("n1 -> n3 [label=\"Y\"];"), "{}", graph);
+    }
+
+    #[test]
+    fn shared_lists() {
+        let input = Value::List(Rc::new(ExactList::new(vec![Value::Number(1), Value::Number(2)])));
+        let v = evaluate(&parse("()(())").expect("parse error"), &input).expect("runtime error");
+        let graph = value(&v);
+        assert!(graph.contains("v0 [label=\"ConcatList\\nlength 4\"];"), "{}", graph);
+        // both sides are the input
+        assert!(graph.contains("v0 -> v1 [label=\"first\"];"), "{}", graph);
+        assert!(graph.contains("v0 -> v1 [label=\"second\"];"), "{}", graph);
+        assert_eq!(graph.matches("ExactList").count(), 1, "{}", graph);
+    }
+
+    #[test]
+    fn thunks() {
+        let v = evaluate(&parse("0(()(1)][()(1))").expect("parse error"), &Value::Number(0)).expect("runtime error");
+        let Value::List(l) = &v else { panic!("not a list") };
+        assert_eq!(l.index(2).ok(), Some(Value::Number(3)));
+        let graph = value(&v);
+        assert!(graph.contains("v0 [label=\"MapList\\nof ()(1)\"];"), "{}", graph);
+        assert!(graph.contains("v0 -> v1 [label=\"source\"];"), "{}", graph);
+        assert!(graph.contains("[label=\"[0..1]\"]"), "{}", graph);
+        assert!(graph.contains("[label=\"[2]\"]"), "{}", graph);
+        assert!(graph.contains("[label=\"[3..]\"]"), "{}", graph);
+        assert_eq!(graph.matches("label=\"thunk\"").count(), 2, "{}", graph);
+    }
+}
@@ -0,0 +1,4 @@
+// Translations of programs into other languages, see `labra-minus compile --emit`.
+
+pub mod dot;
+pub mod rust;
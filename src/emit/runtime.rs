@@ -0,0 +1,549 @@
+// The runtime that programs compiled with `compile --emit rust` are built on. It is a copy
+// of the lazy lists of the interpreter without anything that only makes them faster, so a
+// compiled program prints exactly what the interpreter would.
+// It is written so that it also builds with the 2015 edition, which is what rustc uses
+// when it is not told otherwise.
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+const INFINITE_LIST_PREVIEW_LENGTH: i64 = 3;
+// how many list elements a debug print writes out, like the interpreter's --debug-limit
+const DEBUG_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+enum RuntimeError {
+    OutOfBounds(&'static str, String),
+    ResolvingInfiniteList(&'static str, String),
+    MismatchedTypes(&'static str, String),
+}
+
+impl RuntimeError {
+    fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::OutOfBounds(code, _) |
+            RuntimeError::ResolvingInfiniteList(code, _) |
+            RuntimeError::MismatchedTypes(code, _) => code,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::OutOfBounds(_, s) => write!(f, "Out of Bounds Error - {}", s),
+            RuntimeError::ResolvingInfiniteList(_, s) => write!(f, "Resolving Infinite List Error - {}", s),
+            RuntimeError::MismatchedTypes(_, s) => write!(f, "Mismatched Types - {}", s),
+        }
+    }
+}
+
+fn out_of_bounds(i: i64, len: i64) -> RuntimeError {
+    RuntimeError::OutOfBounds("LM0101", format!("Attempted to access index {} of list of length {}", i, len))
+}
+
+fn infinite_length() -> RuntimeError {
+    RuntimeError::ResolvingInfiniteList("LM0103", String::from("Cannot get length of infinite list"))
+}
+
+fn forcing_infinite_list() -> RuntimeError {
+    RuntimeError::ResolvingInfiniteList("LM0104", String::from("Attempted to force_resolve an infinite list. (Does your final output include one?)"))
+}
+
+type Body = fn(&Value) -> Result<Value, RuntimeError>;
+
+#[derive(Clone)]
+enum Value {
+    Number(i64),
+    List(Rc<dyn List>),
+}
+
+trait List {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError>;
+    fn length(&self) -> Result<i64, RuntimeError>;
+    fn force_resolve(&self) -> Result<(), RuntimeError>;
+}
+
+impl Value {
+    // writes at most budget list elements in total, leaving the rest as ...
+    fn write(&self, budget: &mut usize, s: &mut String) -> Result<(), RuntimeError> {
+        match self {
+            Value::Number(n) => s.push_str(&n.to_string()),
+            Value::List(l) => {
+                let (len, mut cut) = match l.length() {
+                    Err(RuntimeError::ResolvingInfiniteList(..)) => (INFINITE_LIST_PREVIEW_LENGTH, true),
+                    Ok(len) => (len, false),
+                    Err(e) => return Err(e),
+                };
+                s.push('[');
+                for i in 0..len {
+                    if *budget == 0 {
+                        cut = true;
+                        break;
+                    }
+                    *budget -= 1;
+                    if i > 0 {
+                        s.push_str(", ");
+                    }
+                    l.index(i)?.write(budget, s)?;
+                }
+                if cut {
+                    s.push_str(if s.ends_with('[') { "..." } else { ", ..." });
+                }
+                s.push(']');
+            }
+        }
+        Ok(())
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        match self {
+            Value::Number(_) => Ok(()),
+            Value::List(l) => l.force_resolve(),
+        }
+    }
+
+    fn as_string(&self) -> Option<String> {
+        let l = match self {
+            Value::List(l) => l,
+            Value::Number(_) => return None,
+        };
+        let mut s = String::new();
+        for i in 0..l.length().ok()? {
+            match l.index(i).ok()? {
+                Value::Number(n) => s.push(char::from_u32(u32::try_from(n).ok()?)?),
+                Value::List(_) => return None,
+            }
+        }
+        Some(s)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (mut limit, mut s) = (usize::MAX, String::new());
+        match self.write(&mut limit, &mut s) {
+            Ok(()) => write!(f, "{}", s),
+            Err(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (mut limit, mut s) = (usize::MAX, String::new());
+        match self.write(&mut limit, &mut s) {
+            Ok(()) => write!(f, "{}", s),
+            Err(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+// infinite lists are incomparable
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(n1), Value::Number(n2)) => n1 == n2,
+            (Value::List(l1), Value::List(l2)) => lists_equal(l1, l2).unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+fn lists_equal(l1: &Rc<dyn List>, l2: &Rc<dyn List>) -> Result<bool, RuntimeError> {
+    if l1.length()? != l2.length()? {
+        return Ok(false);
+    }
+    for i in 0..l1.length()? {
+        if l1.index(i)? != l2.index(i)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+struct ExactList(Vec<Value>);
+
+impl List for ExactList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        let len = self.length()?;
+        if i >= len || i < -len {
+            return Err(out_of_bounds(i, len));
+        }
+        Ok(self.0[if i >= 0 { i } else { len + i } as usize].clone())
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Ok(self.0.len() as i64)
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        self.0.iter().try_for_each(Value::force_resolve)
+    }
+}
+
+struct EncapsulateList {
+    body: Option<Body>,
+    input: Value,
+    value: RefCell<Option<Result<Value, RuntimeError>>>,
+}
+
+impl EncapsulateList {
+    fn get(&self) -> Result<Value, RuntimeError> {
+        if let Some(v) = self.value.borrow().clone() {
+            return v;
+        }
+        let v = (self.body.expect("elements without a body are evaluated when they are made"))(&self.input);
+        *self.value.borrow_mut() = Some(v.clone());
+        v
+    }
+}
+
+impl List for EncapsulateList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        if i == 0 || i == -1 {
+            self.get()
+        } else {
+            Err(RuntimeError::OutOfBounds("LM0102", format!("Attempted to access index {} of list of length 1", i)))
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Ok(1)
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        self.get().map(|_| ())
+    }
+}
+
+struct ConcatList {
+    first: Rc<dyn List>,
+    second: Rc<dyn List>,
+    firstlen: Option<i64>,
+    length: Option<i64>,
+}
+
+impl List for ConcatList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        // negative indexes go to the first side and indexes past the end to the second
+        match self.firstlen {
+            Some(len) if i >= len => self.second.index(i - len),
+            _ => self.first.index(i),
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        match self.length {
+            Some(len) => Ok(len),
+            None => Ok(self.first.length()? + self.second.length()?),
+        }
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        self.first.force_resolve()?;
+        self.second.force_resolve()
+    }
+}
+
+struct MapList {
+    body: Body,
+    source: Rc<dyn List>,
+    resolved: RefCell<Vec<Option<Value>>>,
+}
+
+impl MapList {
+    fn resolve(&self, i: i64, trueindex: usize) -> Result<Value, RuntimeError> {
+        if let Some(Some(v)) = self.resolved.borrow().get(trueindex) {
+            return Ok(v.clone());
+        }
+        let v = (self.body)(&self.source.index(i)?)?;
+        let mut resolved = self.resolved.borrow_mut();
+        if resolved.len() <= trueindex {
+            resolved.resize(trueindex + 1, None);
+        }
+        resolved[trueindex] = Some(v.clone());
+        Ok(v)
+    }
+}
+
+impl List for MapList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        let len = match self.source.length() {
+            Ok(len) => len,
+            Err(RuntimeError::ResolvingInfiniteList(..)) if i >= 0 => return self.resolve(i, i as usize),
+            Err(e) => return Err(e),
+        };
+        if i >= len || i < -len {
+            return Err(out_of_bounds(i, len));
+        }
+        self.resolve(i, if i >= 0 { i } else { len + i } as usize)
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        self.source.length()
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        let len = match self.source.length() {
+            Ok(len) => len,
+            Err(e) => {
+                self.source.force_resolve()?;
+                return Err(e);
+            }
+        };
+        self.source.force_resolve()?;
+        for i in 0..len {
+            self.resolve(i, i as usize)?;
+        }
+        Ok(())
+    }
+}
+
+struct InductionList {
+    body: Body,
+    resolved: RefCell<Vec<Value>>,
+    fixed_point: RefCell<Option<Value>>,
+}
+
+impl List for InductionList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        if i >= 0 {
+            let i = i as usize;
+            loop {
+                let last = {
+                    let resolved = self.resolved.borrow();
+                    if let Some(v) = resolved.get(i) {
+                        return Ok(v.clone());
+                    }
+                    resolved[resolved.len() - 1].clone()
+                };
+                let next = (self.body)(&last)?;
+                self.resolved.borrow_mut().push(next);
+            }
+        }
+        // negative indexes are the first fixed point reached
+        if let Some(v) = self.fixed_point.borrow().clone() {
+            return Ok(v);
+        }
+        let mut prev = self.resolved.borrow().last().expect("inductions start with a value").clone();
+        loop {
+            let next = (self.body)(&prev)?;
+            if prev == next {
+                *self.fixed_point.borrow_mut() = Some(next.clone());
+                return Ok(next);
+            }
+            prev = next;
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(infinite_length())
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(forcing_infinite_list())
+    }
+}
+
+// the closed forms of inductions whose body is (), a number k, or adds k to its input
+struct ConstantList(Value, Value);
+
+impl List for ConstantList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        Ok(if i == 0 { self.0.clone() } else { self.1.clone() })
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(infinite_length())
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(forcing_infinite_list())
+    }
+}
+
+struct ProgressionList(i64, i64);
+
+impl List for ProgressionList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        if i >= 0 {
+            // overflows like the additions it stands for, panicking unless built with -O
+            let n = self.0 as i128 + self.1 as i128 * i as i128;
+            match i64::try_from(n) {
+                Ok(n) => Ok(Value::Number(n)),
+                Err(_) if cfg!(debug_assertions) => panic!("attempt to add with overflow"),
+                Err(_) => Ok(Value::Number(n as i64)),
+            }
+        } else if self.1 == 0 {
+            Ok(Value::Number(self.0))
+        } else {
+            Err(RuntimeError::ResolvingInfiniteList("LM0108", format!(
+                "Searched for the fixed point of a list that counts up by {} forever", self.1
+            )))
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(infinite_length())
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(forcing_infinite_list())
+    }
+}
+
+enum Step {
+    Identity,
+    Constant(i64),
+    Add(i64),
+    Other,
+}
+
+fn empty() -> Value {
+    Value::List(Rc::new(ExactList(Vec::new())))
+}
+
+fn length(v: Value) -> Result<Value, RuntimeError> {
+    match v {
+        Value::Number(n) => Ok(Value::Number(n.abs())),
+        Value::List(l) => Ok(Value::Number(l.length()?)),
+    }
+}
+
+fn encapsulate(body: Body, input: &Value) -> Value {
+    Value::List(Rc::new(EncapsulateList { body: Some(body), input: input.clone(), value: RefCell::new(None) }))
+}
+
+fn singleton(v: Value) -> Value {
+    Value::List(Rc::new(EncapsulateList { body: None, input: Value::Number(0), value: RefCell::new(Some(Ok(v))) }))
+}
+
+fn add(v1: Value, v2: Value, line: u32) -> Result<Value, RuntimeError> {
+    match (v1, v2) {
+        (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 + n2)),
+        (Value::List(l1), Value::List(l2)) => {
+            let firstlen = l1.length();
+            let length = match (&firstlen, l2.length()) {
+                (Ok(len1), Ok(len2)) => Some(len1 + len2),
+                _ => None,
+            };
+            Ok(Value::List(Rc::new(ConcatList { first: l1, second: l2, firstlen: firstlen.ok(), length })))
+        }
+        _ => Err(RuntimeError::MismatchedTypes("LM0105", format!("Cannot add number and list (line {})", line))),
+    }
+}
+
+fn index(v1: Value, v2: Value, line: u32) -> Result<Value, RuntimeError> {
+    match (v1, v2) {
+        (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 - n2)),
+        (Value::List(l), Value::Number(n)) => l.index(n),
+        _ => Err(RuntimeError::MismatchedTypes("LM0106", format!("Cannot subtract or index with list (line {})", line))),
+    }
+}
+
+fn induction(body: Body, step: Step, init: Value) -> Value {
+    Value::List(match (step, init) {
+        (Step::Identity, init) => Rc::new(ConstantList(init.clone(), init)),
+        (Step::Constant(k), init) => Rc::new(ConstantList(init, Value::Number(k))),
+        (Step::Add(k), Value::Number(start)) => Rc::new(ProgressionList(start, k)),
+        (_, init) => Rc::new(InductionList { body, resolved: RefCell::new(vec![init]), fixed_point: RefCell::new(None) }),
+    })
+}
+
+fn map(body: Body, v: Value, line: u32) -> Result<Value, RuntimeError> {
+    match v {
+        Value::List(source) => Ok(Value::List(Rc::new(MapList { body, source, resolved: RefCell::new(Vec::new()) }))),
+        Value::Number(_) => Err(RuntimeError::MismatchedTypes("LM0107", format!("Attempt to map number on line {}", line))),
+    }
+}
+
+fn debug(v: Value, line: u32, col: u32) -> Value {
+    // writing out v can run other debugs, which have to finish first
+    let (mut budget, mut s) = (DEBUG_LIMIT, String::new());
+    let message = match v.write(&mut budget, &mut s) {
+        Ok(()) => format!("Debug at {}:{} - {}", line, col, s),
+        Err(e) => format!("Debug at {}:{} - {:?}", line, col, e),
+    };
+    eprintln!("{}", message);
+    v
+}
+
+// Inputs can be numbers, lists like [1, [2, 3]], or strings. The default input is 0.
+fn parse_input(s: &str) -> Value {
+    if let Ok(n) = s.parse() {
+        Value::Number(n)
+    } else if let Some(l) = parse_list(s) {
+        l
+    } else {
+        string(s)
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::List(Rc::new(ExactList(s.chars().map(|c| Value::Number(i64::from(u32::from(c)))).collect())))
+}
+
+fn parse_list(s: &str) -> Option<Value> {
+    if !s.starts_with('[') || !s.ends_with(']') {
+        return None;
+    }
+    let mut sections = Vec::new();
+    let mut lastcomma = 0;
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        if i == 0 {
+            continue;
+        } else if i == s.len() - 1 {
+            if depth != 0 {
+                return None;
+            }
+            sections.push(&s[lastcomma + 1..i]);
+            continue;
+        }
+        if c == '[' {
+            depth += 1;
+        } else if c == ']' {
+            depth -= 1;
+            if depth < 0 {
+                return None;
+            }
+        } else if c == ',' && depth == 0 {
+            sections.push(&s[lastcomma + 1..i]);
+            lastcomma = i;
+        }
+    }
+    let values = sections.iter().map(|section| {
+        let section = section.trim();
+        if let Ok(n) = section.parse() {
+            Value::Number(n)
+        } else if let Some(l) = parse_list(section) {
+            l
+        } else {
+            string(section)
+        }
+    });
+    Some(Value::List(Rc::new(ExactList(values.collect()))))
+}
+
+fn main() -> ExitCode {
+    let input = env::args().nth(1).map_or(Value::Number(0), |s| parse_input(&s));
+    match program(&input).and_then(|v| { v.force_resolve()?; Ok(v) }) {
+        Ok(v) => {
+            println!("{}", v);
+            if let Some(s) = v.as_string() {
+                println!("{}", s);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Runtime error[{}]: {}", e.code(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::parsetree::ParseTree;
+use crate::value::{Function, Step};
+
+// the lists and operators every compiled program uses, followed by its functions
+const RUNTIME: &str = include_str!("runtime.rs");
+
+// Translates a program into a Rust program that runs it, which only needs the standard
+// library and can be built with `rustc` on its own. It takes the input as its first argument
+// and prints the same things `labra-minus` does. Every map, induction and encapsulation body
+// becomes a function, numbered in the order they appear, with f0 being the whole program.
+pub fn emit(expression: &ParseTree) -> String {
+    let mut emitter = Emitter { functions: Vec::new() };
+    emitter.function(expression);
+
+    let mut source = String::from("#![allow(dead_code, unused_variables)]\n\n");
+    source.push_str(RUNTIME);
+    source.push_str("\nfn program(input: &Value) -> Result<Value, RuntimeError> {\n    f0(input)\n}\n");
+    for (i, function) in emitter.functions.iter().enumerate() {
+        source.push_str(&format!("\nfn f{}(input: &Value) -> Result<Value, RuntimeError> {{\n", i));
+        if let Some(slots) = function.slots {
+            source.push_str(&format!("    let mut shared: Vec<Option<Value>> = vec![None; {}];\n", slots));
+        }
+        source.push_str(&format!("    Ok({})\n}}\n", function.body));
+    }
+    source
+}
+
+struct EmittedFunction {
+    body: String,
+    // the number of ParseTree::Shared slots used in the body, if there are any
+    slots: Option<usize>,
+}
+
+struct Emitter {
+    functions: Vec<EmittedFunction>,
+}
+
+impl Emitter {
+    // adds a function for a body and returns its index
+    fn function(&mut self, body: &ParseTree) -> usize {
+        let f = self.functions.len();
+        self.functions.push(EmittedFunction { body: String::new(), slots: None });
+        let mut slots = None;
+        let body = self.expression(body, &mut slots);
+        self.functions[f] = EmittedFunction { body, slots };
+        f
+    }
+
+    // A Rust expression for the value of expression, which returns from its function if
+    // there is an error. Arguments are evaluated left to right, like in evaluate.
+    fn expression(&mut self, expression: &ParseTree, slots: &mut Option<usize>) -> String {
+        match expression {
+            ParseTree::Number{n, ..} => format!("Value::Number({}i64)", n),
+
+            ParseTree::Input{..} => String::from("input.clone()"),
+
+            ParseTree::EmptyList{..} => String::from("empty()"),
+
+            ParseTree::Length{arg, ..} => format!("length({})?", self.expression(arg, slots)),
+
+            ParseTree::Encapsulate{arg, ..} => format!("encapsulate(f{}, input)", self.function(arg)),
+
+            ParseTree::Strict{arg, ..} => format!("singleton({})", self.expression(arg, slots)),
+
+            ParseTree::Addition{arg1, arg2, line, ..} => format!(
+                "add({}, {}, {})?",
+                self.expression(arg1, slots), self.expression(arg2, slots), line
+            ),
+
+            ParseTree::IndexSubtraction{arg1, arg2, line, ..} => format!(
+                "index({}, {}, {})?",
+                self.expression(arg1, slots), self.expression(arg2, slots), line
+            ),
+
+            ParseTree::Induction{arg1, arg2, ..} => {
+                let step = match Function::Tree(arg2.clone()).step() {
+                    Some(Step::Identity) => String::from("Step::Identity"),
+                    Some(Step::Constant(k)) => format!("Step::Constant({}i64)", k),
+                    Some(Step::Add(k)) => format!("Step::Add({}i64)", k),
+                    None => String::from("Step::Other"),
+                };
+                let init = self.expression(arg1, slots);
+                format!("induction(f{}, {}, {})", self.function(arg2), step, init)
+            }
+
+            ParseTree::Map{arg1, arg2, line, ..} => {
+                let list = self.expression(arg1, slots);
+                format!("map(f{}, {}, {})?", self.function(arg2), list, line)
+            }
+
+            ParseTree::Debug{arg, line, col} => format!("debug({}, {}, {})", self.expression(arg, slots), line, col),
+
+            ParseTree::Shared{arg, slot} => {
+                *slots = Some(slots.unwrap_or(0).max(slot + 1));
+                format!(
+                    "match shared[{slot}].clone() {{ Some(v) => v, None => {{ let v = {}; shared[{slot}] = Some(v.clone()); v }} }}",
+                    self.expression(arg, slots), slot = slot
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process::{self, Command};
+
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::{cse, optimize};
+    use crate::output;
+    use crate::parsetree::parse;
+    use crate::strictness;
+    use crate::sync::Rc;
+    use crate::value::{string, ExactList, Value};
+
+    // what `labra-minus FILE INPUT` prints to stdout and stderr
+    fn interpret(pt: &ParseTree, input: &str) -> (String, String) {
+        let input = if let Ok(n) = input.parse() {
+            Value::Number(n)
+        } else if let Ok(l) = input.parse::<ExactList>() {
+            Value::List(Rc::new(l))
+        } else {
+            string::string_to_list(input).unwrap_or(Value::Number(0))
+        };
+        let (result, debugs) = output::capture(|| evaluate(pt, &input)
+            .and_then(|v| { v.force_resolve()?; Ok(v) }));
+        let mut printed = String::new();
+        match result {
+            Ok(v) => {
+                printed.push_str(&format!("{}\n", v));
+                if let Ok(s) = string::list_to_string(&v) {
+                    printed.push_str(&format!("{}\n", s));
+                }
+            }
+            Err(e) => printed.push_str(&format!("Runtime error[{}]: {}\n", e.code(), e)),
+        }
+        (printed, debugs)
+    }
+
+    #[test]
+    fn emitted_functions() {
+        let source = emit(&parse("0(()(1)][()[1][])").expect("parse error"));
+        assert!(source.contains("fn f0(input: &Value) -> Result<Value, RuntimeError> {\n    Ok(map(f2, induction(f1, Step::Add(1i64), Value::Number(0i64)), 1)?)\n}"));
+        assert!(source.contains("fn f2(input: &Value) -> Result<Value, RuntimeError> {\n    Ok(encapsulate(f3, input))\n}"));
+    }
+
+    // builds every example, and these programs, with rustc and checks that it prints what
+    // the interpreter does
+    #[test]
+    fn compiled_examples() {
+        let programs = ["1[](2[])[0[1]]", "[](5[])[0[1]]", "1[](2[])[5]", "()([])[5]"];
+        let inputs = ["0", "7", "[7, 2]", "Hello, World!"];
+        let build = env::temp_dir().join(format!("labra-minus-emit-{}", process::id()));
+        fs::create_dir_all(&build).expect("could not create build directory");
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut sources: Vec<(String, String)> = programs.iter().enumerate()
+            .map(|(i, p)| (format!("program{}", i), p.to_string()))
+            .collect();
+        for entry in fs::read_dir(dir).expect("could not read examples") {
+            let path = entry.expect("could not read examples").path();
+            let source = fs::read_to_string(&path).expect("could not read example");
+            let name = path.file_stem().expect("examples have names").to_string_lossy().into_owned();
+            sources.push((name, source));
+        }
+        for (name, source) in sources {
+            let pt = Rc::new(parse(&source).expect("parse error"));
+            let marked = strictness::mark(&pt);
+            // shared subexpressions are only made when optimizing
+            let optimized = cse::share(&strictness::mark(&optimize::optimize(&pt)));
+
+            for (variant, pt) in [("", marked), ("-O", optimized)] {
+                let (rs, binary) = (build.join(format!("{}{}.rs", name, variant)), build.join(format!("{}{}", name, variant)));
+                fs::write(&rs, emit(&pt)).expect("could not write emitted source");
+                let built = Command::new(&rustc).arg("-o").arg(&binary).arg(&rs)
+                    .output()
+                    .expect("could not run rustc");
+                let warnings = String::from_utf8_lossy(&built.stderr);
+                assert!(built.status.success(), "{} {} did not build:\n{}", name, variant, warnings);
+                // emitted programs should build without warnings too
+                assert!(warnings.is_empty(), "{} {} built with warnings:\n{}", name, variant, warnings);
+
+                for input in inputs {
+                    let run = Command::new(&binary).arg(input).output().expect("could not run compiled example");
+                    let (expected, debugs) = interpret(&pt, input);
+                    let failed = expected.starts_with("Runtime error[");
+                    assert_eq!(String::from_utf8_lossy(&run.stdout), expected, "{} {} with input {}", name, variant, input);
+                    assert_eq!(String::from_utf8_lossy(&run.stderr), debugs, "{} {} with input {}", name, variant, input);
+                    assert_eq!(run.status.success(), !failed, "{} {} with input {}", name, variant, input);
+                }
+            }
+        }
+        fs::remove_dir_all(&build).expect("could not remove build directory");
+    }
+}
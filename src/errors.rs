@@ -1,30 +1,82 @@
 use std::fmt;
 
+// Stable identifiers for every kind of error, see `labra-minus --explain CODE`.
+// Parse errors get one code per variant, runtime errors carry the code for the
+// situation they came from since one variant can be raised in several places.
+pub mod codes {
+    pub const INVALID_CHARACTER: &str = "LM0001";
+    pub const NUMBER_PARSE: &str = "LM0002";
+    pub const SYNTAX: &str = "LM0003";
+    pub const UNMATCHED_BRACKET: &str = "LM0004";
+    pub const EMPTY_FILE: &str = "LM0005";
+
+    pub const FINITE_OUT_OF_BOUNDS: &str = "LM0101";
+    pub const ENCAPSULATE_OUT_OF_BOUNDS: &str = "LM0102";
+    pub const INFINITE_LENGTH: &str = "LM0103";
+    pub const FORCING_INFINITE_LIST: &str = "LM0104";
+    pub const ADD_NUMBER_AND_LIST: &str = "LM0105";
+    pub const SUBTRACT_OR_INDEX_WITH_LIST: &str = "LM0106";
+    pub const MAP_NUMBER: &str = "LM0107";
+    pub const NO_FIXED_POINT: &str = "LM0108";
+}
+
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum ParseError {
     InvalidCharacter(String),
-    UnexpectedEOF,
     NumberParseError(String),
     SyntaxError(String),
+    UnmatchedBracket(String),
     EmptyFile,
 }
 
 #[derive(Debug, Clone)]
 pub enum RuntimeError {
-    OutOfBounds(String),
-    ResolvingInfiniteList(String),
-    MismatchedTypes(String),
+    OutOfBounds(&'static str, String),
+    ResolvingInfiniteList(&'static str, String),
+    MismatchedTypes(&'static str, String),
     // NegativeIndex(String),
 }
 
+impl ParseError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::InvalidCharacter(_) => codes::INVALID_CHARACTER,
+            ParseError::NumberParseError(_) => codes::NUMBER_PARSE,
+            ParseError::SyntaxError(_) => codes::SYNTAX,
+            ParseError::UnmatchedBracket(_) => codes::UNMATCHED_BRACKET,
+            ParseError::EmptyFile => codes::EMPTY_FILE,
+        }
+    }
+}
+
+impl RuntimeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::OutOfBounds(code, _) |
+            RuntimeError::ResolvingInfiniteList(code, _) |
+            RuntimeError::MismatchedTypes(code, _) => code,
+        }
+    }
+
+    // the name of the variant, which tests can expect instead of a code
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeError::OutOfBounds(..) => "OutOfBounds",
+            RuntimeError::ResolvingInfiniteList(..) => "ResolvingInfiniteList",
+            RuntimeError::MismatchedTypes(..) => "MismatchedTypes",
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}",
             match self{
                 ParseError::InvalidCharacter(s) => "Invalid Character Error - ".to_owned() + s,
-                ParseError::UnexpectedEOF =>  "Unexpected EOF".to_owned(),
                 ParseError::NumberParseError(s) => "Number Parse Error - ".to_owned() + s,
                 ParseError::SyntaxError(s) => "Syntax Error - ".to_owned() + s,
+                ParseError::UnmatchedBracket(s) => "Unmatched Bracket - ".to_owned() + s,
                 ParseError::EmptyFile => "Empty File".to_owned()
             }
         )
@@ -35,9 +87,33 @@ impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}",
             match self{
-                RuntimeError::OutOfBounds(s) => "Out of Bounds Error - ".to_owned() + s,
-                RuntimeError::ResolvingInfiniteList(s) => "Resolving Infinite List Error - ".to_owned() + s,
-                RuntimeError::MismatchedTypes(s) => "Mismatched Types - ".to_owned() + s,
+                RuntimeError::OutOfBounds(_, s) => "Out of Bounds Error - ".to_owned() + s,
+                RuntimeError::ResolvingInfiniteList(_, s) => "Resolving Infinite List Error - ".to_owned() + s,
+                RuntimeError::MismatchedTypes(_, s) => "Mismatched Types - ".to_owned() + s,
+            }
+        )
+    }
+}
+
+// type errors are the statically detected versions of runtime errors, so they share their codes
+#[derive(Debug, Clone)]
+pub enum TypeError {
+    MismatchedTypes(&'static str, String),
+}
+
+impl TypeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::MismatchedTypes(code, _) => code,
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}",
+            match self{
+                TypeError::MismatchedTypes(_, s) => "Mismatched Types - ".to_owned() + s,
             }
         )
     }
@@ -1,67 +1,112 @@
-use std::rc::Rc;
-
 use super::parsetree::ParseTree;
 use super::value;
-use super::value::{ConcatList, InductionList, MapList, Value};
-use super::errors::RuntimeError;
+use super::value::{inductionlist, maplist, ConcatList, Value};
+use super::errors::{codes, RuntimeError};
+use super::observer;
+use super::stats;
+use super::sync::Rc;
 
 pub fn evaluate(expression: &ParseTree, input: &Value) -> Result<Value, RuntimeError> {
+    evaluate_in_scope(expression, input, &mut Vec::new())
+}
+
+// shared holds the values of the ParseTree::Shared slots evaluated so far in this scope
+fn evaluate_in_scope(expression: &ParseTree, input: &Value, shared: &mut Vec<Option<Value>>) -> Result<Value, RuntimeError> {
+    if matches!(expression, ParseTree::Shared{..}) {
+        return evaluate_node(expression, input, shared);
+    }
+    stats::count_node();
+    if !observer::active() {
+        return evaluate_node(expression, input, shared);
+    }
+    observer::enter_node(expression, input);
+    let result = evaluate_node(expression, input, shared);
+    observer::exit_node(expression, &result);
+    result
+}
+
+fn evaluate_node(expression: &ParseTree, input: &Value, shared: &mut Vec<Option<Value>>) -> Result<Value, RuntimeError> {
     match expression {
-        ParseTree::Number{n, line: _} => Ok(Value::Number(n.clone())),
+        ParseTree::Number{n, line: _, col: _} => Ok(Value::Number(*n)),
 
-        ParseTree::Input{line: _} => Ok(input.clone()),
+        ParseTree::Input{line: _, col: _} => Ok(input.clone()),
 
-        ParseTree::EmptyList{line: _} => Ok(Value::List(Rc::new(value::ExactList::new(Vec::new())))),
+        ParseTree::EmptyList{line: _, col: _} => Ok(Value::List(Rc::new(value::ExactList::new(Vec::new())))),
 
-        ParseTree::Length{arg, line: _} => match evaluate(arg, input)? {
+        ParseTree::Length{arg, line: _, col: _} => match evaluate_in_scope(arg, input, shared)? {
             Value::Number(n) => Ok(Value::Number(n.abs())),
-            Value::List(l) => Ok(Value::Number(l.length()? as i64)),
+            Value::List(l) => Ok(Value::Number(l.length()?)),
         },
 
-        ParseTree::Encapsulate{arg, line: _} => {
-            Ok(Value::List(Rc::new(value::EncapsulateList::new(*arg.to_owned(), input.clone()))))
+        ParseTree::Encapsulate{arg, line: _, col: _} => {
+            observer::thunk_created(expression);
+            Ok(Value::List(Rc::new(value::EncapsulateList::new(arg.clone(), input.clone()))))
         }
 
-        ParseTree::Addition{arg1, arg2, line} => match (evaluate(arg1, input)?, evaluate(arg2, input)?) {
-            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 + n2)),
-            (Value::List(l1), Value::List(l2)) => {
-                Ok(Value::List(Rc::new(ConcatList::new(l1, l2))))
+        ParseTree::Strict{arg, line: _, col: _} => {
+            let eval = evaluate_in_scope(arg, input, shared)?;
+            Ok(Value::List(Rc::new(value::EncapsulateList::evaluated(eval))))
+        }
+
+        ParseTree::Addition{arg1, arg2, line, col: _} => {
+            match (evaluate_in_scope(arg1, input, shared)?, evaluate_in_scope(arg2, input, shared)?) {
+                (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 + n2)),
+                (Value::List(l1), Value::List(l2)) => {
+                    Ok(Value::List(ConcatList::concat(l1, l2)))
+                }
+                _ => Err(RuntimeError::MismatchedTypes(codes::ADD_NUMBER_AND_LIST, format!(
+                    "Cannot add number and list (line {})",
+                    line
+                ))),
             }
-            _ => Err(RuntimeError::MismatchedTypes(format!(
-                "Cannot add number and list (line {})",
-                line
-            ))),
-        },
+        }
 
-        ParseTree::IndexSubtraction{arg1, arg2, line} => {
-            match (evaluate(arg1, input)?, evaluate(arg2, input)?) {
+        ParseTree::IndexSubtraction{arg1, arg2, line, col: _} => {
+            match (evaluate_in_scope(arg1, input, shared)?, evaluate_in_scope(arg2, input, shared)?) {
                 (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 - n2)),
                 (Value::List(l), Value::Number(n)) => {
                     Ok(l.index(n)?)
                 }
-                _ => Err(RuntimeError::MismatchedTypes(format!(
+                _ => Err(RuntimeError::MismatchedTypes(codes::SUBTRACT_OR_INDEX_WITH_LIST, format!(
                     "Cannot subtract or index with list (line {})",
                     line
                 ))),
             }
         }
 
-        ParseTree::Induction{arg1, arg2, line: _} => Ok(Value::List(Rc::new(InductionList::new(
-            (**arg2).clone(),
-            evaluate(arg1, input)?,
-        )))),
+        ParseTree::Induction{arg1, arg2, line: _, col: _} => {
+            let init = evaluate_in_scope(arg1, input, shared)?;
+            observer::thunk_created(expression);
+            Ok(Value::List(inductionlist::induction(arg2.clone(), init)))
+        }
 
-        ParseTree::Map{arg1, arg2, line} => match evaluate(arg1, input)? {
-            Value::List(l) => Ok(Value::List(Rc::new(MapList::new((**arg2).clone(), l)))),
-            _ => Err(RuntimeError::MismatchedTypes(format!(
+        ParseTree::Map{arg1, arg2, line, col: _} => match evaluate_in_scope(arg1, input, shared)? {
+            Value::List(l) => {
+                observer::thunk_created(expression);
+                Ok(Value::List(maplist::map(arg2.clone(), l)))
+            }
+            _ => Err(RuntimeError::MismatchedTypes(codes::MAP_NUMBER, format!(
                 "Attempt to map number on line {}",
                 line
             ))),
         },
 
         ParseTree::Debug{arg, line, col} => {
-            let eval = evaluate(arg, input)?;
-            println!("Debug at {}:{} - {:?}", line, col, eval);
+            let eval = evaluate_in_scope(arg, input, shared)?;
+            observer::debug(*line, *col, &eval);
+            Ok(eval)
+        }
+
+        ParseTree::Shared{arg, slot} => {
+            if let Some(Some(eval)) = shared.get(*slot) {
+                stats::count_shared_reuse();
+                return Ok(eval.clone());
+            }
+            let eval = evaluate_in_scope(arg, input, shared)?;
+            if shared.len() <= *slot {
+                shared.resize(*slot + 1, None);
+            }
+            shared[*slot] = Some(eval.clone());
             Ok(eval)
         }
     }
@@ -74,7 +119,7 @@ mod tests {
     #[test]
     fn single_number() {
         let result =
-            evaluate(&ParseTree::Number{n: 0, line: 0}, &Value::Number(0)).expect("evaluation failure");
+            evaluate(&ParseTree::Number{n: 0, line: 0, col: 0}, &Value::Number(0)).expect("evaluation failure");
         if let Value::Number(n) = result {
             assert_eq!(n, 0);
         } else {
@@ -85,7 +130,7 @@ mod tests {
     #[test]
     fn single_input() {
         let mut result =
-            evaluate(&ParseTree::Input{line: 0}, &Value::Number(5)).expect("evaluation failure");
+            evaluate(&ParseTree::Input{line: 0, col: 0}, &Value::Number(5)).expect("evaluation failure");
         if let Value::Number(n) = result {
             assert_eq!(n, 5);
         } else {
@@ -94,7 +139,7 @@ mod tests {
 
         let newlist = vec![Value::Number(5)];
         result = evaluate(
-            &ParseTree::Input{line: 0},
+            &ParseTree::Input{line: 0, col: 0},
             &Value::List(Rc::new(value::ExactList::new(newlist))),
         )
         .expect("evaluation failure");
@@ -114,7 +159,7 @@ mod tests {
     #[test]
     fn single_emptylist() {
         let result =
-            evaluate(&ParseTree::EmptyList{line: 0}, &Value::Number(99)).expect("evaluation failure");
+            evaluate(&ParseTree::EmptyList{line: 0, col: 0}, &Value::Number(99)).expect("evaluation failure");
         if let Value::List(l) = result {
             let len = l.length().expect("indexing failure");
             assert_eq!(len, 0);
@@ -126,7 +171,7 @@ mod tests {
     #[test]
     fn single_encapsulate() {
         let result = evaluate(
-            &ParseTree::Encapsulate{arg: Box::new(ParseTree::Number{n:7, line:0}), line:0},
+            &ParseTree::Encapsulate{arg: Rc::new(ParseTree::Number{n:7, line: 0, col: 0}), line: 0, col: 0},
             &Value::Number(99),
         )
         .expect("evaluation failure");
@@ -146,7 +191,7 @@ mod tests {
     #[test]
     fn single_length() {
         let mut result = evaluate(
-            &ParseTree::Length{arg: Box::new(ParseTree::Number{n:4, line:0}), line: 0},
+            &ParseTree::Length{arg: Rc::new(ParseTree::Number{n:4, line: 0, col: 0}), line: 0, col: 0},
             &Value::Number(0),
         )
         .expect("evaluation failure");
@@ -157,7 +202,7 @@ mod tests {
         }
 
         result = evaluate(
-            &ParseTree::Length{arg: Box::new(ParseTree::Number{n: -94, line: 0}), line: 0},
+            &ParseTree::Length{arg: Rc::new(ParseTree::Number{n: -94, line: 0, col: 0}), line: 0, col: 0},
             &Value::Number(0),
         )
         .expect("evaluation failure");
@@ -168,7 +213,7 @@ mod tests {
         }
 
         result = evaluate(
-            &ParseTree::Length{arg: Box::new(ParseTree::EmptyList{line: 0}), line: 0},
+            &ParseTree::Length{arg: Rc::new(ParseTree::EmptyList{line: 0, col: 0}), line: 0, col: 0},
             &Value::Number(0),
         )
         .expect("evaluation failure");
@@ -179,11 +224,11 @@ mod tests {
         }
 
         result = evaluate(
-            &ParseTree::Length{arg: Box::new(
-                ParseTree::Encapsulate{arg: Box::new(
-                    ParseTree::Number{n: 34, line: 0},
-                ), line: 0}
-            ), line: 0},
+            &ParseTree::Length{arg: Rc::new(
+                ParseTree::Encapsulate{arg: Rc::new(
+                    ParseTree::Number{n: 34, line: 0, col: 0},
+                ), line: 0, col: 0}
+            ), line: 0, col: 0},
             &Value::Number(0),
         )
         .expect("evaluation failure");
@@ -198,14 +243,15 @@ mod tests {
     fn invalid_operation_test() {
         let mut result = evaluate(
             &ParseTree::Addition{
-                arg1: Box::new(ParseTree::Number{n:4, line: 0}),
-                arg2: Box::new(ParseTree::EmptyList{line: 1}),
-                line: 2
+                arg1: Rc::new(ParseTree::Number{n:4, line: 0, col: 0}),
+                arg2: Rc::new(ParseTree::EmptyList{line: 1, col: 0}),
+                line: 2, col: 0
             },
             &Value::Number(0),
         );
         if let Err(e) = result{
-            if let RuntimeError::MismatchedTypes(s) = e{
+            if let RuntimeError::MismatchedTypes(code, s) = e{
+                assert_eq!(code, codes::ADD_NUMBER_AND_LIST);
                 assert_eq!(s, String::from("Cannot add number and list (line 2)"));
             }else{
                 panic!("wrong error");
@@ -216,9 +262,9 @@ mod tests {
 
         result = evaluate(
             &ParseTree::Addition{
-                arg1: Box::new(ParseTree::EmptyList{line: 0}),
-                arg2: Box::new(ParseTree::Number{n: 4, line: 0}),
-                line: 0
+                arg1: Rc::new(ParseTree::EmptyList{line: 0, col: 0}),
+                arg2: Rc::new(ParseTree::Number{n: 4, line: 0, col: 0}),
+                line: 0, col: 0
             },
             &Value::Number(0),
         );
@@ -226,9 +272,9 @@ mod tests {
 
         result = evaluate(
             &ParseTree::IndexSubtraction{
-                arg1: Box::new(ParseTree::Number{n:4, line: 0}),
-                arg2: Box::new(ParseTree::EmptyList{line: 0}),
-                line: 0
+                arg1: Rc::new(ParseTree::Number{n:4, line: 0, col: 0}),
+                arg2: Rc::new(ParseTree::EmptyList{line: 0, col: 0}),
+                line: 0, col: 0
             },
             &Value::Number(0),
         );
@@ -236,13 +282,29 @@ mod tests {
 
         result = evaluate(
             &ParseTree::IndexSubtraction{
-                arg1: Box::new(ParseTree::EmptyList{line: 0}),
-                arg2: Box::new(ParseTree::Number{n: 4, line: 0}),
-                line: 0
+                arg1: Rc::new(ParseTree::EmptyList{line: 0, col: 0}),
+                arg2: Rc::new(ParseTree::Number{n: 4, line: 0, col: 0}),
+                line: 0, col: 0
             },
             &Value::Number(0),
         );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn bodies_are_shared() {
+        let body = Rc::new(ParseTree::Input{line: 0, col: 0});
+        let tree = ParseTree::Map{
+            arg1: Rc::new(ParseTree::EmptyList{line: 0, col: 0}),
+            arg2: body.clone(),
+            line: 0,
+            col: 0
+        };
+        let result = evaluate(&tree, &Value::Number(0)).expect("evaluation failure");
+        // the map list holds on to the same body instead of a copy of it
+        assert_eq!(Rc::strong_count(&body), 3);
+        drop(result);
+        assert_eq!(Rc::strong_count(&body), 2);
+    }
     // tests for more complicated operations will use parse, and thus will be in main
 }
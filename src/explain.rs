@@ -0,0 +1,251 @@
+use super::errors::codes;
+
+// Long-form explanations of each error code, printed by `labra-minus --explain CODE`
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.to_uppercase();
+    Some(match &code[..] {
+        codes::INVALID_CHARACTER => "\
+LM0001: Invalid character
+
+Only digits, the brackets ( ) [ ], the debug operator ! and whitespace are
+allowed outside of comments. Negative numbers, operators like + and -, and
+commas don't exist; everything is built out of bracket operations.
+
+Failing example:
+
+    1(-5)
+
+Fixed example (subtract 5 from 0 instead of writing -5):
+
+    1(0[5])
+",
+        codes::NUMBER_PARSE => "\
+LM0002: Number parse error
+
+Numbers have to fit in a 64 bit signed integer.
+
+Failing example:
+
+    99999999999999999999
+
+Fixed example (build large numbers with addition instead):
+
+    9999999999999999(9999999999999999)
+",
+        codes::SYNTAX => "\
+LM0003: Syntax error
+
+Every expression has one of the forms N, {}, X{} or X{Y}, where { is
+either bracket. This error is raised when a bracket pair or ! has nothing
+before it but needs something (like (5) or [)), or when a number appears
+after an expression instead of leading it.
+
+Failing example:
+
+    (5)
+
+Fixed example (add 5 to 1):
+
+    1(5)
+",
+        codes::UNMATCHED_BRACKET => "\
+LM0004: Unmatched bracket
+
+An open bracket was never closed before the end of the file, or a close
+bracket appeared without anything to close. The error points at the
+unmatched bracket and suggests where its closer probably belongs, based on
+how the program is indented.
+
+Failing example:
+
+    0[](
+        1[]
+
+Fixed example:
+
+    0[](
+        1[]
+    )
+",
+        codes::EMPTY_FILE => "\
+LM0005: Empty file
+
+The program doesn't contain any code, only whitespace and comments.
+
+Failing example:
+
+    # returns its input
+
+Fixed example:
+
+    # returns its input
+    ()
+",
+        codes::FINITE_OUT_OF_BOUNDS => "\
+LM0101: Index out of bounds of a finite list
+
+A list of length n can be indexed with 0 to n-1, or with -1 to -n to count
+from the end. Any other index is out of bounds. This code is used for the
+input list, the empty list and lists created by mapping, which have the same
+length as the list they map over. Indexing past the end of a list built by
+concatenating X[] values reaches the last X[] and raises LM0102 instead.
+
+Failing example:
+
+    1[](2[])[()(1))[2]
+
+Fixed example:
+
+    1[](2[])[()(1))[1]
+",
+        codes::ENCAPSULATE_OUT_OF_BOUNDS => "\
+LM0102: Index out of bounds of an encapsulated value
+
+X[] is a list containing only X, so the only valid indices are 0 and -1.
+
+Failing example:
+
+    5[][1]
+
+Fixed example:
+
+    5[][0]
+",
+        codes::INFINITE_LENGTH => "\
+LM0103: Length of an infinite list
+
+Lists created with induction X(Y] are infinite, so they have no length and
+X(Y]() fails. Anything that needs the length, like negative indexing through
+a map or concatenation, fails in the same way.
+
+Failing example:
+
+    0(()(1)]()
+
+Fixed example (take the length of a finite list instead):
+
+    0[](1[])()
+",
+        codes::FORCING_INFINITE_LIST => "\
+LM0104: Forcing an infinite list in the final output
+
+The output of a program is fully evaluated before it is printed, which is
+impossible if it contains an infinite list made with induction. Index the
+list to pick out the elements you need.
+
+Failing example:
+
+    0(()(1)]
+
+Fixed example:
+
+    0(()(1)][5]
+",
+        codes::ADD_NUMBER_AND_LIST => "\
+LM0105: Adding a number and a list
+
+X(Y) adds two numbers or concatenates two lists. Mixing the two is an
+error. To append a number to a list, encapsulate it first.
+
+Failing example:
+
+    1[](2)
+
+Fixed example:
+
+    1[](2[])
+",
+        codes::SUBTRACT_OR_INDEX_WITH_LIST => "\
+LM0106: Subtracting or indexing with a list
+
+X[Y] is X - Y when X is a number and the Yth element when X is a list, but
+in both cases Y has to be a number.
+
+Failing example:
+
+    1[](2[])[0[]]
+
+Fixed example:
+
+    1[](2[])[0]
+",
+        codes::MAP_NUMBER => "\
+LM0107: Mapping a number
+
+X[Y) applies Y to every element of the list X, so X has to be a list.
+
+Failing example:
+
+    5[()(1))
+
+Fixed example:
+
+    5[][()(1))
+",
+        codes::NO_FIXED_POINT => "\
+LM0108: Induction without a fixed point
+
+Negatively indexing an induction X(Y] searches for the first element that Y
+leaves unchanged. Some inductions, like counting up with ()(1), can be seen to
+never reach one, so the search fails instead of running forever. Others
+still run forever.
+
+Failing example:
+
+    0(()(1)][0[1]]
+
+Fixed example (index a specific element instead):
+
+    0(()(1)][10]
+",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+    use crate::value::Value;
+
+    const ALL_CODES: [&str; 13] = [
+        codes::INVALID_CHARACTER, codes::NUMBER_PARSE, codes::SYNTAX, codes::UNMATCHED_BRACKET,
+        codes::EMPTY_FILE, codes::FINITE_OUT_OF_BOUNDS, codes::ENCAPSULATE_OUT_OF_BOUNDS,
+        codes::INFINITE_LENGTH, codes::FORCING_INFINITE_LIST, codes::ADD_NUMBER_AND_LIST,
+        codes::SUBTRACT_OR_INDEX_WITH_LIST, codes::MAP_NUMBER, codes::NO_FIXED_POINT,
+    ];
+
+    // the indented lines after "Failing example:" and "Fixed example..."
+    fn example(explanation: &str, heading: &str) -> String {
+        explanation.split(heading).nth(1).expect("missing example")
+            .lines()
+            .skip_while(|l| !l.starts_with("    "))
+            .take_while(|l| l.starts_with("    ") || l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn run(program: &str) -> Result<(), &'static str> {
+        let pt = parse(program).map_err(|e| e.code())?;
+        evaluate(&pt, &Value::Number(0))
+            .and_then(|v| v.force_resolve())
+            .map_err(|e| e.code())
+    }
+
+    #[test]
+    fn examples_match_codes() {
+        for code in ALL_CODES {
+            let explanation = explain(code).expect("missing explanation");
+            assert!(explanation.starts_with(code));
+            assert_eq!(run(&example(explanation, "Failing example")), Err(code), "{}", code);
+            assert_eq!(run(&example(explanation, "Fixed example")), Ok(()), "{}", code);
+        }
+    }
+
+    #[test]
+    fn unknown_code() {
+        assert!(explain("LM9999").is_none());
+        assert!(explain("lm0001").is_some());
+    }
+}
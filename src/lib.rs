@@ -0,0 +1,23 @@
+pub mod coverage;
+pub mod cse;
+pub mod debugger;
+#[cfg(test)]
+mod differential;
+pub mod emit;
+pub mod errors;
+pub mod evaluate;
+pub mod explain;
+pub mod lint;
+pub mod observer;
+pub mod optimize;
+pub mod output;
+pub mod parsetree;
+pub mod profile;
+pub mod stats;
+pub mod strictness;
+pub mod sync;
+pub mod testing;
+pub mod trace;
+pub mod typecheck;
+pub mod value;
+pub mod vm;
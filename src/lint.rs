@@ -0,0 +1,252 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::parsetree::{self, NodeId, ParseTree, Span};
+
+pub const DEBUG_OPERATOR: &str = "debug-operator";
+pub const CONSTANT_FUNCTION: &str = "constant-function";
+pub const LEADING_ZERO: &str = "leading-zero";
+pub const NEGATIVE_INDUCTION_INDEX: &str = "negative-induction-index";
+pub const UNREACHABLE_CONCAT: &str = "unreachable-concat";
+pub const TOP_LEVEL_INPUT: &str = "top-level-input";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub id: &'static str,
+    pub line: u32,
+    pub col: u32,
+    // the whole flagged node, the same span a trace gives it. line and col are still where
+    // the node itself is, like its ! or bracket, which is what allow comments apply to.
+    pub span: Span,
+    pub message: String,
+}
+
+pub struct LintOptions {
+    // if false, any () outside of a map or induction body is reported
+    pub takes_input: bool,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (l1, c1, l2, c2) = self.span;
+        write!(f, "warning[{}] at {}:{}-{}:{} - {}", self.id, l1, c1, l2, c2, self.message)
+    }
+}
+
+// Lints a parsed program. The source is needed for things the ParseTree doesn't keep,
+// like how numbers were written and `# lint: allow(...)` comments.
+// An allow comment silences the listed lints on its own line and on the line after it.
+pub fn lint(source: &str, expression: &ParseTree, options: &LintOptions) -> Vec<Lint> {
+    let mut linter = Linter {
+        source_lines: source.lines().collect(),
+        spans: parsetree::spans(expression, source),
+        options,
+        lints: Vec::new(),
+    };
+    linter.walk(expression, false);
+
+    let allowed = allowed_lints(source);
+    let mut lints: Vec<Lint> = linter.lints.into_iter()
+        .filter(|l| !allowed.contains(&(l.line, l.id.to_owned())))
+        .collect();
+    lints.sort_by_key(|l| (l.line, l.col));
+    lints
+}
+
+// (line, lint id) pairs silenced by allow comments
+fn allowed_lints(source: &str) -> HashSet<(u32, String)> {
+    let mut allowed = HashSet::new();
+    for (i, l) in source.lines().enumerate() {
+        let linenum = i as u32 + 1;
+        let comment = match l.find('#') {
+            Some(start) => l[start + 1..].trim(),
+            None => continue,
+        };
+        let ids = match comment.strip_prefix("lint:")
+            .map(|s| s.trim())
+            .and_then(|s| s.strip_prefix("allow("))
+            .and_then(|s| s.split(')').next())
+        {
+            Some(ids) => ids,
+            None => continue,
+        };
+        for id in ids.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            allowed.insert((linenum, id.to_owned()));
+            allowed.insert((linenum + 1, id.to_owned()));
+        }
+    }
+    allowed
+}
+
+struct Linter<'a> {
+    source_lines: Vec<&'a str>,
+    spans: HashMap<NodeId, Span>,
+    options: &'a LintOptions,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, id: &'static str, expression: &ParseTree, message: String) {
+        let (line, col) = expression.position();
+        let span = self.spans.get(&expression.id()).copied().unwrap_or((line, col, line, col));
+        self.lints.push(Lint{id, line, col, span, message});
+    }
+
+    // in_body is true inside map and induction bodies, where () is the function argument
+    fn walk(&mut self, expression: &ParseTree, in_body: bool) {
+        match expression {
+            ParseTree::Number{..} => {
+                let digits = self.source_text(expression);
+                if digits.len() > 1 && digits.starts_with('0') {
+                    self.warn(LEADING_ZERO, expression, format!(
+                        "number {} is written with a leading zero", digits
+                    ));
+                }
+            }
+
+            ParseTree::Input{..} => {
+                if !in_body && !self.options.takes_input {
+                    self.warn(TOP_LEVEL_INPUT, expression, String::from(
+                        "() is used outside of a function body, but the program takes no input"
+                    ));
+                }
+            }
+
+            ParseTree::EmptyList{..} => (),
+
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} |
+            ParseTree::Strict{arg, ..} => self.walk(arg, in_body),
+
+            ParseTree::Addition{arg1, arg2, ..} => {
+                if is_infinite(arg1) {
+                    self.warn(UNREACHABLE_CONCAT, expression, String::from(
+                        "concatenating onto an infinite list, so the right side can never be reached"
+                    ));
+                }
+                self.walk(arg1, in_body);
+                self.walk(arg2, in_body);
+            }
+
+            ParseTree::IndexSubtraction{arg1, arg2, ..} => {
+                if let (true, Some(n)) = (is_induction(arg1), constant_number(arg2)) {
+                    if n < 0 {
+                        self.warn(NEGATIVE_INDUCTION_INDEX, expression, String::from(
+                            "negatively indexing an induction searches for a fixed point and may not terminate"
+                        ));
+                    }
+                }
+                self.walk(arg1, in_body);
+                self.walk(arg2, in_body);
+            }
+
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => {
+                if !arg2.uses_input() {
+                    let kind = if let ParseTree::Map{..} = expression { "map" } else { "induction" };
+                    self.warn(CONSTANT_FUNCTION, expression, format!(
+                        "{} body never uses (), so it is a constant function", kind
+                    ));
+                }
+                self.walk(arg1, in_body);
+                self.walk(arg2, true);
+            }
+
+            ParseTree::Debug{arg, ..} => {
+                self.warn(DEBUG_OPERATOR, expression, String::from("debug operator left in code"));
+                self.walk(arg, in_body);
+            }
+
+            ParseTree::Shared{arg, ..} => self.walk(arg, in_body),
+        }
+    }
+
+    fn source_text(&self, expression: &ParseTree) -> String {
+        let (line, col) = expression.position();
+        self.source_lines.get(line as usize - 1)
+            .map(|l| l.chars()
+                .skip(col as usize - 1)
+                .take_while(|c| c.is_ascii_digit())
+                .collect())
+            .unwrap_or_default()
+    }
+}
+
+fn is_induction(expression: &ParseTree) -> bool {
+    match expression {
+        ParseTree::Induction{..} => true,
+        ParseTree::Debug{arg, ..} | ParseTree::Shared{arg, ..} => is_induction(arg),
+        _ => false,
+    }
+}
+
+fn is_infinite(expression: &ParseTree) -> bool {
+    match expression {
+        ParseTree::Induction{..} => true,
+        ParseTree::Map{arg1, ..} | ParseTree::Debug{arg: arg1, ..} |
+        ParseTree::Shared{arg: arg1, ..} => is_infinite(arg1),
+        ParseTree::Addition{arg1, arg2, ..} => is_infinite(arg1) || is_infinite(arg2),
+        _ => false,
+    }
+}
+
+// the value of expressions built only out of numbers, like 0[1], unless it overflows
+fn constant_number(expression: &ParseTree) -> Option<i64> {
+    match expression {
+        ParseTree::Number{n, ..} => Some(*n),
+        ParseTree::Length{arg, ..} => constant_number(arg)?.checked_abs(),
+        ParseTree::Addition{arg1, arg2, ..} => constant_number(arg1)?.checked_add(constant_number(arg2)?),
+        ParseTree::IndexSubtraction{arg1, arg2, ..} => constant_number(arg1)?.checked_sub(constant_number(arg2)?),
+        ParseTree::Debug{arg, ..} | ParseTree::Shared{arg, ..} => constant_number(arg),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsetree::parse;
+
+    fn lint_ids(source: &str, takes_input: bool) -> Vec<&'static str> {
+        let pt = parse(source).expect("parse error");
+        lint(source, &pt, &LintOptions{takes_input}).iter().map(|l| l.id).collect()
+    }
+
+    #[test]
+    fn clean_program() {
+        assert!(lint_ids("2(()(1)][5]", true).is_empty());
+    }
+
+    #[test]
+    fn individual_lints() {
+        assert_eq!(lint_ids("1!(2)", true), vec![DEBUG_OPERATOR]);
+        assert_eq!(lint_ids("0[][1)", true), vec![CONSTANT_FUNCTION]);
+        assert_eq!(lint_ids("007", true), vec![LEADING_ZERO]);
+        assert_eq!(lint_ids("0(()]", true), Vec::<&str>::new());
+        assert_eq!(lint_ids("0(()][0[1]]", true), vec![NEGATIVE_INDUCTION_INDEX]);
+        assert_eq!(lint_ids("0(()(1)]([])", true), vec![UNREACHABLE_CONCAT]);
+        assert_eq!(lint_ids("()(1)", false), vec![TOP_LEVEL_INPUT]);
+        assert_eq!(lint_ids("0[][()(1))", false), Vec::<&str>::new());
+        assert_eq!(lint_ids("0(()(1)][9223372036854775807(1)]", true), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn lint_positions() {
+        let source = "1\n(2)!";
+        let pt = parse(source).expect("parse error");
+        let lints = lint(source, &pt, &LintOptions{takes_input: true});
+        assert_eq!(lints.len(), 1);
+        assert_eq!((lints[0].line, lints[0].col), (2, 4));
+        assert_eq!(lints[0].span, (1, 1, 2, 4));
+        assert_eq!(lints[0].to_string(), "warning[debug-operator] at 1:1-2:4 - debug operator left in code");
+    }
+
+    #[test]
+    fn allow_comments() {
+        assert_eq!(lint_ids("1!(2) # lint: allow(debug-operator)", true), Vec::<&str>::new());
+        assert_eq!(lint_ids("# lint: allow(leading-zero, debug-operator)\n01!", true), Vec::<&str>::new());
+        assert_eq!(lint_ids("# lint: allow(leading-zero)\n01!", true), vec![DEBUG_OPERATOR]);
+        assert_eq!(lint_ids("# lint: allow(debug-operator)\n\n1!", true), vec![DEBUG_OPERATOR]);
+        // comments go by where the ! is, not where the code it wraps starts
+        assert_eq!(lint_ids("1\n# lint: allow(debug-operator)\n(2)!", true), Vec::<&str>::new());
+        assert_eq!(lint_ids("# lint: allow(debug-operator)\n1\n\n(2)!", true), vec![DEBUG_OPERATOR]);
+    }
+}
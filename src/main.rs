@@ -1,13 +1,14 @@
+use std::cell::RefCell;
 use std::env;
 use std::fs;
-use std::rc::Rc;
+use labra_minus::sync::Rc;
 use std::process::ExitCode;
 
-mod evaluate;
-mod parsetree;
-mod value;
-use value::string;
-mod errors;
+use labra_minus::{coverage, cse, debugger, emit, evaluate, explain, lint, observer, optimize, output, parsetree, profile, stats, strictness, testing, trace, typecheck, value, vm};
+use labra_minus::observer::Observer;
+use labra_minus::output::{DebugFormat, DebugSettings};
+use labra_minus::parsetree::ParseTree;
+use labra_minus::value::string;
 
 fn main() -> ExitCode{
     let args: Vec<String> = env::args().collect();
@@ -16,50 +17,497 @@ fn main() -> ExitCode{
         return ExitCode::FAILURE;
     }
 
-    let filepath = &args[1];
-    let contents;
-    match fs::read_to_string(filepath) {
-        Ok(s) => {
-            contents = s;
+    match &args[1][..] {
+        "check" => check(&args[2..]),
+        "lint" => lint(&args[2..]),
+        "compile" => compile(&args[2..]),
+        "coverage" => coverage(&args[2..]),
+        "debug" => debug(&args[2..]),
+        "replay" => replay(&args[2..]),
+        "test" => test(&args[2..]),
+        "--explain" => explain(&args[2..]),
+        _ => run(&args[1..]),
+    }
+}
+
+enum Backend {
+    // evaluate::evaluate walking the ParseTree
+    Tree,
+    // compile to bytecode and run it in vm::run
+    Vm,
+}
+
+// flags can go anywhere, everything else is the filepath and then the input
+struct RunOptions {
+    backend: Backend,
+    optimize: bool,
+    // skips strictness::mark, so every X[] is lazy
+    lazy_all: bool,
+    stats: bool,
+    // how far apart the kept elements of induction lists are, see InductionList
+    checkpoint: usize,
+    // threads used to force the elements of maps, see MapList::force_resolve
+    jobs: usize,
+    // prints a profile of the run to stderr
+    profile: bool,
+    // where to write the profile as collapsed stacks for flame graphs
+    profile_stacks: Option<String>,
+    // the lcov tracefile to add the nodes evaluated in this run to
+    coverage: Option<String>,
+    // where to write the events of the run, see trace
+    trace: Option<String>,
+    // how ! prints, see output::DebugSettings
+    debug: DebugSettings,
+    // where ! prints to instead of stderr
+    debug_out: Option<String>,
+    filepath: String,
+    input: Option<String>,
+}
+
+fn parse_run_options(args: &[String]) -> Result<RunOptions, String> {
+    let mut backend = Backend::Tree;
+    let mut optimize = false;
+    let mut lazy_all = false;
+    let mut stats = false;
+    let mut checkpoint = 1;
+    let mut jobs = 1;
+    let mut profile = false;
+    let mut profile_stacks = None;
+    let mut coverage = None;
+    let mut trace = None;
+    let mut debug = DebugSettings::default();
+    let mut debug_out = None;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--backend" => {
+                backend = match args.next().map(|s| &s[..]) {
+                    Some("tree") => Backend::Tree,
+                    Some("vm") => Backend::Vm,
+                    Some(other) => return Err(format!("unknown backend \"{}\", expected tree or vm", other)),
+                    None => return Err(String::from("--backend needs a value, either tree or vm")),
+                };
+            }
+            "-O" => optimize = true,
+            "-O0" => optimize = false,
+            "--lazy-all" => lazy_all = true,
+            "--stats" => stats = true,
+            "--checkpoint" => {
+                checkpoint = match args.next().map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => n,
+                    _ => return Err(String::from("--checkpoint needs a positive number")),
+                };
+            }
+            "--jobs" => {
+                jobs = match args.next().map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => n,
+                    _ => return Err(String::from("--jobs needs a positive number")),
+                };
+                if jobs > 1 && !cfg!(feature = "parallel") {
+                    return Err(String::from("--jobs needs labra-minus to be built with the parallel feature"));
+                }
+            }
+            "--profile" => profile = true,
+            "--profile-stacks" => {
+                profile_stacks = Some(args.next()
+                    .ok_or_else(|| String::from("--profile-stacks needs a file to write to"))?
+                    .clone());
+            }
+            "--coverage" => {
+                coverage = Some(args.next()
+                    .ok_or_else(|| String::from("--coverage needs a tracefile to write to"))?
+                    .clone());
+            }
+            "--trace" => {
+                trace = Some(args.next()
+                    .ok_or_else(|| String::from("--trace needs a file to write to"))?
+                    .clone());
+            }
+            "--no-debug" => debug.enabled = false,
+            "--debug-out" => {
+                debug_out = Some(args.next()
+                    .ok_or_else(|| String::from("--debug-out needs a file to write to"))?
+                    .clone());
+            }
+            "--debug-format" => {
+                debug.format = match args.next().map(|s| &s[..]) {
+                    Some("text") => DebugFormat::Text,
+                    Some("json") => DebugFormat::Json,
+                    Some(other) => return Err(format!("unknown debug format \"{}\", expected text or json", other)),
+                    None => return Err(String::from("--debug-format needs a value, either text or json")),
+                };
+            }
+            "--debug-limit" => {
+                debug.limit = match args.next().map(|s| s.parse::<usize>()) {
+                    Some(Ok(n)) => n,
+                    _ => return Err(String::from("--debug-limit needs a number of elements")),
+                };
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if coverage.is_some() {
+        // nodes removed by the optimizer could never be evaluated
+        if optimize {
+            return Err(String::from("--coverage can't be combined with -O"));
+        }
+        // and X[] should only count as evaluated when its element is needed
+        lazy_all = true;
+    }
+    // profiles and traces should show the nodes the program asks for, so X[] stays lazy,
+    // like it does for coverage
+    if profile || profile_stacks.is_some() || trace.is_some() {
+        lazy_all = true;
+    }
+    // the vm doesn't know which node it is running, and other threads aren't recorded
+    if profile || profile_stacks.is_some() || coverage.is_some() {
+        if let Backend::Vm = backend {
+            return Err(String::from("profiling needs the tree backend"));
         }
+        if jobs > 1 {
+            return Err(String::from("profiling can't be combined with --jobs"));
+        }
+    }
+    if trace.is_some() {
+        if let Backend::Vm = backend {
+            return Err(String::from("--trace needs the tree backend"));
+        }
+        if jobs > 1 {
+            return Err(String::from("--trace can't be combined with --jobs"));
+        }
+    }
+    let mut positional = positional.into_iter();
+    Ok(RunOptions {
+        backend,
+        optimize,
+        lazy_all,
+        stats,
+        checkpoint,
+        jobs,
+        profile,
+        profile_stacks,
+        coverage,
+        trace,
+        debug,
+        debug_out,
+        filepath: positional.next().ok_or_else(|| String::from("Please provide an filepath"))?,
+        input: positional.next(),
+    })
+}
+
+fn run(args: &[String]) -> ExitCode {
+    let options = match parse_run_options(args) {
+        Ok(options) => options,
         Err(e) => {
-            println!("could not read file {}: {:?}", filepath, e);
+            println!("{}", e);
             return ExitCode::FAILURE;
         }
+    };
+    let source = match read_source(&options.filepath) {
+        Some(s) => s,
+        None => return ExitCode::FAILURE,
+    };
+    let parsedfile = match parse_source(&source) {
+        Some(pt) => pt,
+        None => return ExitCode::FAILURE,
+    };
+    let input = read_input(options.input.as_ref());
+    stats::reset();
+    value::inductionlist::set_checkpoint_interval(options.checkpoint);
+    // closed forms don't evaluate the bodies of inductions like 0(()(1)], which would
+    // then never count as covered, show up in a profile or be traced
+    let observed = options.profile || options.profile_stacks.is_some() || options.coverage.is_some() || options.trace.is_some();
+    value::inductionlist::set_closed_forms(!observed);
+    value::maplist::set_jobs(options.jobs);
+    output::configure(options.debug);
+    if let Some(path) = &options.debug_out {
+        match fs::File::create(path) {
+            Ok(file) => output::set_sink(Box::new(std::io::BufWriter::new(file))),
+            Err(e) => {
+                println!("could not write file {}: {:?}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let mut parsedfile = Rc::new(parsedfile);
+    if options.optimize {
+        parsedfile = optimize::optimize(&parsedfile);
+    }
+    if !options.lazy_all {
+        parsedfile = strictness::mark(&parsedfile);
+    }
+    // sharing has to come last, since strictness changes which scope X[] is evaluated in
+    if options.optimize {
+        parsedfile = cse::share(&parsedfile);
     }
 
-    // parse
-    let parsedfile;
-    match parsetree::parse(&contents[..]) {
-        Ok(pt) => {
-            parsedfile = pt;
+    // evaluate
+    // coverage is the number of evaluations in the profile
+    let profiling = options.profile || options.profile_stacks.is_some() || options.coverage.is_some();
+    let mut observers: Vec<Observer> = Vec::new();
+    let profiler = std::rc::Rc::new(RefCell::new(profile::Profiler::default()));
+    if profiling {
+        observers.push(profiler.clone());
+    }
+    let mut tracer = None;
+    if let Some(path) = &options.trace {
+        match fs::File::create(path) {
+            Ok(file) => {
+                let t = std::rc::Rc::new(RefCell::new(trace::Tracer::new(&parsedfile, &source, Box::new(std::io::BufWriter::new(file)))));
+                observers.push(t.clone());
+                tracer = Some(t);
+            }
+            Err(e) => {
+                println!("could not write file {}: {:?}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let output = observer::observe(&observers, || match options.backend {
+        Backend::Tree => evaluate::evaluate(&parsedfile, &input),
+        Backend::Vm => vm::run(&Rc::new(vm::compile(&parsedfile)), 0, &input),
+    }.and_then(|v|{v.force_resolve()?; Ok(v)}));
+    if let Some(path) = &options.debug_out {
+        if let Err(e) = output::finish() {
+            eprintln!("could not write file {}: {:?}", path, e);
+        }
+    }
+    if let (Some(tracer), Some(path)) = (tracer, &options.trace) {
+        if let Err(e) = tracer.borrow_mut().finish() {
+            eprintln!("could not write file {}: {:?}", path, e);
+        }
+    }
+    if options.stats {
+        eprintln!("{}", stats::get());
+    }
+    if profiling {
+        let profile = profiler.borrow_mut().finish(&parsedfile);
+        if options.profile {
+            eprint!("{}\n{}", profile.report(&parsedfile), profile.annotate(&source));
+        }
+        if let Some(path) = &options.profile_stacks {
+            if let Err(e) = fs::write(path, profile.collapsed_stacks(&parsedfile)) {
+                eprintln!("could not write file {}: {:?}", path, e);
+            }
+        }
+        if let Some(path) = &options.coverage {
+            let mut tracefile = match read_tracefile(path) {
+                Some(tracefile) => tracefile,
+                None => return ExitCode::FAILURE,
+            };
+            tracefile.record(&options.filepath, &parsedfile, &profile);
+            if let Err(e) = fs::write(path, tracefile.to_string()) {
+                eprintln!("could not write file {}: {:?}", path, e);
+            }
+        }
+    }
+    match output {
+        Ok(v) => {
+            println!("{}", v);
+            if let Ok(s) = string::list_to_string(&v) {
+                println!("{}", s);
+            }
+            ExitCode::SUCCESS
         }
         Err(e) => {
-            println!("Parsing error: {}", e);
+            println!("Runtime error[{}]: {}", e.code(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn check(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide an filepath to check.\n >> cargo labra-minus -- check your/filepath/here.txt");
+        return ExitCode::FAILURE;
+    }
+    let parsedfile = match read_program(&args[0]) {
+        Some(pt) => pt,
+        None => return ExitCode::FAILURE,
+    };
+    let input = read_input(args.get(1));
+
+    let report = typecheck::check(&parsedfile, typecheck::Type::of(&input));
+    let mut index_kinds: Vec<_> = report.index_kinds.iter().collect();
+    index_kinds.sort_by_key(|(pos, _)| **pos);
+    for ((line, col), kind) in index_kinds {
+        println!("{}:{} - {}", line, col, kind);
+    }
+    println!("Result type: {}", report.result);
+    for e in &report.errors {
+        println!("Type error[{}]: {}", e.code(), e);
+    }
+    // these don't fail the check, since the program may run fine
+    for e in &report.warnings {
+        println!("Possible type error[{}]: {}, if the body it is in is run", e.code(), e);
+    }
+    if report.errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn lint(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide an filepath to lint.\n >> cargo labra-minus -- lint your/filepath/here.txt [--no-input]");
+        return ExitCode::FAILURE;
+    }
+    let contents = match read_source(&args[0]) {
+        Some(s) => s,
+        None => return ExitCode::FAILURE,
+    };
+    let parsedfile = match parse_source(&contents) {
+        Some(pt) => pt,
+        None => return ExitCode::FAILURE,
+    };
+    let options = lint::LintOptions {
+        takes_input: !args[1..].iter().any(|a| a == "--no-input"),
+    };
+
+    let lints = lint::lint(&contents, &parsedfile, &options);
+    for l in &lints {
+        println!("{}", l);
+    }
+    if lints.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// compile --emit rust|dot|dot-values [-O] [-o OUTPUT] FILE [input], which writes to stdout
+// without -o. Only dot-values runs the program, on input.
+fn compile(args: &[String]) -> ExitCode {
+    let usage = "Please provide a language to emit and a filepath to compile.\n >> cargo labra-minus -- compile --emit rust your/filepath/here.txt [-o out.rs]\n >> cargo labra-minus -- compile --emit dot-values your/filepath/here.txt [input]";
+    let mut language = None;
+    let mut optimize = false;
+    let mut output = None;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--emit" => match args.next().map(|s| &s[..]) {
+                Some(l @ ("rust" | "dot" | "dot-values")) => language = Some(l),
+                Some(other) => {
+                    println!("unknown language \"{}\", expected rust, dot or dot-values", other);
+                    return ExitCode::FAILURE;
+                }
+                None => {
+                    println!("--emit needs a value, such as rust");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-O" => optimize = true,
+            "-O0" => optimize = false,
+            "-o" => output = args.next(),
+            _ => positional.push(arg),
+        }
+    }
+    let (filepath, language) = match (positional.first(), language) {
+        (Some(filepath), Some(language)) => (filepath, language),
+        _ => {
+            println!("{}", usage);
             return ExitCode::FAILURE;
         }
+    };
+    let source = match read_source(filepath) {
+        Some(s) => s,
+        None => return ExitCode::FAILURE,
+    };
+    let mut parsedfile = match parse_source(&source) {
+        Some(pt) => Rc::new(pt),
+        None => return ExitCode::FAILURE,
+    };
+    if optimize {
+        parsedfile = optimize::optimize(&parsedfile);
+    }
+    parsedfile = strictness::mark(&parsedfile);
+    if optimize {
+        parsedfile = cse::share(&parsedfile);
+    }
+
+    let emitted = match language {
+        "dot" => emit::dot::program(&parsedfile, &source),
+        // the value of the program before anything forces it
+        "dot-values" => match evaluate::evaluate(&parsedfile, &read_input(positional.get(1).copied())) {
+            Ok(v) => emit::dot::value(&v),
+            Err(e) => {
+                println!("Runtime error[{}]: {}", e.code(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => emit::rust::emit(&parsedfile),
+    };
+    match output {
+        Some(path) => match fs::write(path, emitted) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                println!("could not write file {}: {:?}", path, e);
+                ExitCode::FAILURE
+            }
+        },
+        None => {
+            print!("{}", emitted);
+            ExitCode::SUCCESS
+        }
     }
+}
 
-    // input
-    let input;
-    if args.len() >= 3 {
-        let rawinput = &args[2];
-        if let Ok(n) = rawinput.parse() {
-            input = value::Value::Number(n);
-        } else if let Ok(l) = rawinput.parse::<value::ExactList>() {
-            input = value::Value::List(Rc::new(l));
-        } else if let Ok(l) = string::string_to_list(rawinput) {
-            input = l;
-        } else {
-            input = value::Value::Number(0);
+// coverage FILE TRACEFILE shows which parts of a program the runs recorded with
+// --coverage TRACEFILE never evaluated
+fn coverage(args: &[String]) -> ExitCode {
+    if args.len() < 2 {
+        println!("Please provide an filepath and the tracefile written by --coverage.\n >> cargo labra-minus -- coverage your/filepath/here.txt coverage.info");
+        return ExitCode::FAILURE;
+    }
+    let source = match read_source(&args[0]) {
+        Some(s) => s,
+        None => return ExitCode::FAILURE,
+    };
+    let tracefile = match read_tracefile(&args[1]) {
+        Some(tracefile) => tracefile,
+        None => return ExitCode::FAILURE,
+    };
+    // the program may have been run with a different path to it
+    let counts = match tracefile.programs.get(&args[0]) {
+        Some(counts) => counts,
+        None if tracefile.programs.len() == 1 => tracefile.programs.values().next().expect("there is one program"),
+        None => {
+            println!("{} has no coverage for {}", args[1], args[0]);
+            return ExitCode::FAILURE;
         }
+    };
+    print!("{}", coverage::annotate(&source, counts));
+    if counts.values().all(|c| *c > 0) {
+        ExitCode::SUCCESS
     } else {
-        input = value::Value::Number(0);
+        ExitCode::FAILURE
     }
+}
 
-    // evaluate
-    let output = evaluate::evaluate(&parsedfile, &input)
-        .and_then(|v|{v.force_resolve()?; Ok(v)});
+// debug FILE [input] runs a program with the tree walker, stopping at its first node to
+// take commands from stdin
+fn debug(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide an filepath to debug.\n >> cargo labra-minus -- debug your/filepath/here.txt [input]");
+        return ExitCode::FAILURE;
+    }
+    // the program runs as written, with X[] lazy and no closed forms, so breakpoints in
+    // the bodies of inductions like 0(()(1)] are still reached
+    let parsedfile = match read_program(&args[0]) {
+        Some(pt) => Rc::new(pt),
+        None => return ExitCode::FAILURE,
+    };
+    let input = read_input(args.get(1));
+    value::inductionlist::set_closed_forms(false);
+    println!("debugging {}, type help for a list of commands", args[0]);
+    let debugger = debugger::Debugger::new(parsedfile.clone(), Box::new(std::io::stdin().lock()), Box::new(std::io::stdout()));
+    let observers: [Observer; 1] = [std::rc::Rc::new(RefCell::new(debugger))];
+    let output = observer::observe(&observers, || {
+        evaluate::evaluate(&parsedfile, &input).and_then(|v|{v.force_resolve()?; Ok(v)})
+    });
     match output {
         Ok(v) => {
             println!("{}", v);
@@ -69,12 +517,191 @@ fn main() -> ExitCode{
             ExitCode::SUCCESS
         }
         Err(e) => {
-            println!("Runtime error: {}", e);
+            println!("Runtime error[{}]: {}", e.code(), e);
             ExitCode::FAILURE
         }
     }
 }
 
+// replay TRACEFILE goes through a trace written by --trace, taking commands from stdin
+fn replay(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide the file written by --trace.\n >> cargo labra-minus -- replay trace.jsonl");
+        return ExitCode::FAILURE;
+    }
+    let contents = match read_source(&args[0]) {
+        Some(s) => s,
+        None => return ExitCode::FAILURE,
+    };
+    let trace: trace::Trace = match contents.parse() {
+        Ok(trace) => trace,
+        Err(trace::ParseTraceError(e)) => {
+            println!("could not read trace {}: {}", args[0], e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match trace.replay(&mut std::io::stdin().lock(), &mut std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            println!("{:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn explain(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide an error code to explain.\n >> cargo labra-minus -- --explain LM0001");
+        return ExitCode::FAILURE;
+    }
+    match explain::explain(&args[0]) {
+        Some(explanation) => {
+            print!("{}", explanation);
+            ExitCode::SUCCESS
+        }
+        None => {
+            println!("{} is not a labra-minus error code", args[0]);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// test FILE... runs the tests written into programs as comments, see testing::TestCase.
+// Directories are searched for .txt files. Fails if any test does.
+fn test(args: &[String]) -> ExitCode {
+    if args.is_empty() {
+        println!("Please provide the programs to test.\n >> cargo labra-minus -- test your/filepath/here.txt examples/");
+        return ExitCode::FAILURE;
+    }
+    let mut filepaths = Vec::new();
+    for arg in args {
+        match fs::read_dir(arg) {
+            Ok(entries) => {
+                let mut programs: Vec<String> = entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.extension().is_some_and(|e| e == "txt"))
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect();
+                programs.sort();
+                filepaths.extend(programs);
+            }
+            Err(_) => filepaths.push(arg.clone()),
+        }
+    }
+
+    let (mut passed, mut failed) = (0, 0);
+    for filepath in &filepaths {
+        let source = match read_source(filepath) {
+            Some(s) => s,
+            None => {
+                failed += 1;
+                continue;
+            }
+        };
+        let cases = match testing::cases(&source) {
+            Ok(cases) => cases,
+            Err(e) => {
+                println!("{}: {}", filepath, e);
+                failed += 1;
+                continue;
+            }
+        };
+        if cases.is_empty() {
+            continue;
+        }
+        let parsedfile = match parse_source(&source) {
+            Some(pt) => strictness::mark(&Rc::new(pt)),
+            None => {
+                failed += cases.len();
+                continue;
+            }
+        };
+        for case in &cases {
+            match testing::run(&parsedfile, case) {
+                Ok(()) => {
+                    println!("{}:{}: {} ... ok", filepath, case.line, case.description);
+                    passed += 1;
+                }
+                Err(failures) => {
+                    println!("{}:{}: {} ... FAILED", filepath, case.line, case.description);
+                    for f in failures {
+                        println!("    {}", f);
+                    }
+                    failed += 1;
+                }
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", passed, failed);
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn read_program(filepath: &String) -> Option<ParseTree> {
+    read_source(filepath).and_then(|contents| parse_source(&contents))
+}
+
+fn read_source(filepath: &String) -> Option<String> {
+    match fs::read_to_string(filepath) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            println!("could not read file {}: {:?}", filepath, e);
+            None
+        }
+    }
+}
+
+fn parse_source(contents: &str) -> Option<ParseTree> {
+    match parsetree::parse_all(contents) {
+        Ok(pt) => Some(pt),
+        Err(errors) => {
+            for e in errors {
+                println!("Parsing error[{}]: {}", e.code(), e);
+            }
+            None
+        }
+    }
+}
+
+// a tracefile that doesn't exist yet is empty
+fn read_tracefile(path: &String) -> Option<coverage::Tracefile> {
+    let contents = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            println!("could not read file {}: {:?}", path, e);
+            return None;
+        }
+    };
+    match contents.parse() {
+        Ok(tracefile) => Some(tracefile),
+        Err(coverage::ParseTracefileError(e)) => {
+            println!("could not read tracefile {}: {}", path, e);
+            None
+        }
+    }
+}
+
+// Inputs can be numbers, lists like [1, [2, 3]], or strings. The default input is 0.
+fn read_input(rawinput: Option<&String>) -> value::Value {
+    if let Some(rawinput) = rawinput {
+        if let Ok(n) = rawinput.parse() {
+            value::Value::Number(n)
+        } else if let Ok(l) = rawinput.parse::<value::ExactList>() {
+            value::Value::List(Rc::new(l))
+        } else if let Ok(l) = string::string_to_list(rawinput) {
+            l
+        } else {
+            value::Value::Number(0)
+        }
+    } else {
+        value::Value::Number(0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,196 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use super::errors::RuntimeError;
+use super::output;
+use super::parsetree::ParseTree;
+use super::value::{Function, Value};
+
+// Callbacks from the tree walker, for tools that watch a program run like the profiler, the
+// tracer and the debugger. Every method does nothing by default.
+//
+// Observers are kept per thread, so elements forced on other threads with --jobs aren't
+// seen. While an observer is being called no observers are installed, so anything it
+// evaluates itself (like the debugger showing an element) runs without being observed.
+pub trait EvalObserver {
+    // node is about to be evaluated with () being input
+    fn on_enter_node(&mut self, _node: &ParseTree, _input: &Value) {}
+    // node evaluated to value. Every node entered either exits or fails with on_error.
+    fn on_exit_node(&mut self, _node: &ParseTree, _value: &Value) {}
+    // node failed with error, which may have come from one of the nodes it evaluated
+    fn on_error(&mut self, _node: &ParseTree, _error: &RuntimeError) {}
+    // node, a map, induction or X[], made a lazy list
+    fn on_thunk_created(&mut self, _node: &ParseTree) {}
+    // Element index of a lazy list is about to be computed by calling f. kind is the sort of
+    // list, like "map". Searching an induction for its fixed point computes element -1.
+    fn on_element_forcing(&mut self, _kind: &'static str, _index: i64, _f: &Function) {}
+    // and has been computed
+    fn on_element_forced(&mut self, _kind: &'static str, _index: i64, _f: &Function, _result: &Result<Value, RuntimeError>) {}
+    // A debug operator printed value. Returning true stops it from being printed, so
+    // embedders can send debug output somewhere else.
+    fn on_debug(&mut self, _line: u32, _col: u32, _value: &Value) -> bool {
+        false
+    }
+}
+
+// Observers only ever see values on the thread they are installed on, so they don't have to
+// be thread safe even with the parallel feature.
+pub type Observer = Rc<RefCell<dyn EvalObserver>>;
+
+thread_local! {
+    // whether there are observers to call, checked before anything else so that evaluating
+    // without them costs nothing more
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+    static OBSERVERS: RefCell<Vec<Observer>> = const { RefCell::new(Vec::new()) };
+}
+
+// Runs f with the observers installed, along with any already installed outside of it.
+// Lazy lists can compute their elements long after evaluate returns, so f should also force
+// whatever it needs observed.
+pub fn observe<T>(observers: &[Observer], f: impl FnOnce() -> T) -> T {
+    if observers.is_empty() {
+        return f();
+    }
+    OBSERVERS.with(|o| o.borrow_mut().extend(observers.iter().cloned()));
+    ACTIVE.with(|a| a.set(true));
+    let result = f();
+    OBSERVERS.with(|o| {
+        let mut installed = o.borrow_mut();
+        installed.retain(|i| !observers.iter().any(|observer| Rc::ptr_eq(i, observer)));
+        ACTIVE.with(|a| a.set(!installed.is_empty()));
+    });
+    result
+}
+
+pub(crate) fn active() -> bool {
+    ACTIVE.with(Cell::get)
+}
+
+// calls f on every observer, with the observers taken out while it does
+fn notify(mut f: impl FnMut(&mut dyn EvalObserver)) {
+    let observers = OBSERVERS.with(|o| std::mem::take(&mut *o.borrow_mut()));
+    if observers.is_empty() {
+        return;
+    }
+    ACTIVE.with(|a| a.set(false));
+    for observer in &observers {
+        f(&mut *observer.borrow_mut());
+    }
+    OBSERVERS.with(|o| *o.borrow_mut() = observers);
+    ACTIVE.with(|a| a.set(true));
+}
+
+pub(crate) fn enter_node(node: &ParseTree, input: &Value) {
+    notify(|o| o.on_enter_node(node, input));
+}
+
+pub(crate) fn exit_node(node: &ParseTree, result: &Result<Value, RuntimeError>) {
+    match result {
+        Ok(value) => notify(|o| o.on_exit_node(node, value)),
+        Err(error) => notify(|o| o.on_error(node, error)),
+    }
+}
+
+pub(crate) fn thunk_created(node: &ParseTree) {
+    if active() {
+        notify(|o| o.on_thunk_created(node));
+    }
+}
+
+// computes an element of a lazy list by calling f, see Function::call_element
+pub(crate) fn element(
+    kind: &'static str, index: i64, f: &Function,
+    compute: impl FnOnce() -> Result<Value, RuntimeError>
+) -> Result<Value, RuntimeError> {
+    if !active() {
+        return compute();
+    }
+    notify(|o| o.on_element_forcing(kind, index, f));
+    let result = compute();
+    notify(|o| o.on_element_forced(kind, index, f, &result));
+    result
+}
+
+// prints the output of a debug operator, unless an observer takes it or debugs are off
+pub fn debug(line: u32, col: u32, value: &Value) {
+    if !output::enabled() {
+        return;
+    }
+    let mut taken = false;
+    if active() {
+        notify(|o| taken |= o.on_debug(line, col, value));
+    }
+    if !taken {
+        output::debug(line, col, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl EvalObserver for Recorder {
+        fn on_enter_node(&mut self, node: &ParseTree, input: &Value) {
+            self.events.push(format!("enter {} {:?}", node, input));
+        }
+
+        // writing out a lazy list would compute its elements
+        fn on_exit_node(&mut self, node: &ParseTree, value: &Value) {
+            match value {
+                Value::Number(n) => self.events.push(format!("exit {} {}", node, n)),
+                Value::List(_) => self.events.push(format!("exit {} list", node)),
+            }
+        }
+
+        fn on_error(&mut self, node: &ParseTree, error: &RuntimeError) {
+            self.events.push(format!("error {} {}", node, error.code()));
+        }
+
+        fn on_thunk_created(&mut self, node: &ParseTree) {
+            self.events.push(format!("thunk {}", node));
+        }
+
+        fn on_element_forced(&mut self, kind: &'static str, index: i64, _f: &Function, result: &Result<Value, RuntimeError>) {
+            self.events.push(format!("{} {} {:?}", kind, index, result.as_ref().ok()));
+        }
+
+        fn on_debug(&mut self, line: u32, col: u32, value: &Value) -> bool {
+            self.events.push(format!("debug {}:{} {:?}", line, col, value));
+            true
+        }
+    }
+
+    #[test]
+    fn callbacks() {
+        let pt = parse("0(()!(1)][1]").expect("parse error");
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let observers: [Observer; 1] = [recorder.clone()];
+        let (result, printed) = output::capture(|| observe(&observers, || evaluate(&pt, &Value::Number(0))));
+        assert_eq!(result.expect("runtime error"), Value::Number(1));
+        // the debug output was taken by the recorder
+        assert_eq!(printed, "");
+        assert!(!active());
+        let events = &recorder.borrow().events;
+        assert_eq!(events[0], "enter 0(()!(1)][1] 0");
+        assert!(events.contains(&String::from("thunk 0(()!(1)]")), "{:?}", events);
+        assert!(events.contains(&String::from("debug 1:5 0")), "{:?}", events);
+        assert!(events.contains(&String::from("induction 1 Some(1)")), "{:?}", events);
+        assert_eq!(events.last().map(|e| &e[..]), Some("exit 0(()!(1)][1] 1"));
+    }
+
+    #[test]
+    fn errors() {
+        let pt = parse("1(2[])").expect("parse error");
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let result = observe(&[recorder.clone() as Observer], || evaluate(&pt, &Value::Number(0)));
+        assert!(result.is_err());
+        assert_eq!(recorder.borrow().events.last().map(|e| &e[..]), Some("error 1(2[]) LM0105"));
+    }
+}
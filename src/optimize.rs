@@ -0,0 +1,172 @@
+use super::parsetree::ParseTree;
+use super::sync::Rc;
+
+// Simplifies a program without changing what it outputs, which errors it raises, or when
+// debug operators print. Works bottom up, so each node is simplified after its children.
+//  - arithmetic on constants is folded, like 1(0[5]) -> -4
+//  - list literals built out of X[] and concatenation can be indexed and measured directly,
+//    like 5[](6[])[1] -> 6 and 5[](6[])() -> 2
+//  - X(Y][k] with constant X and k and a Y that ignores its input is X or Y
+// Other subtrees that don't use () aren't evaluated ahead of time, since that could take as
+// long as running them, or fail where a run would never reach them. [](X), X([]) and a(k)[k]
+// are kept too: [] changes which list negative and out of bounds indexes reach, and a(k) can
+// overflow.
+// Nodes that are replaced by a constant keep the position of the node they replace.
+pub fn optimize(expression: &Rc<ParseTree>) -> Rc<ParseTree> {
+    let node = optimize_children(expression);
+    simplify(&node).unwrap_or(node)
+}
+
+fn optimize_children(expression: &Rc<ParseTree>) -> Rc<ParseTree> {
+    match &**expression {
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => expression.clone(),
+
+        ParseTree::Length{arg, line, col} => unary(expression, arg, |arg| ParseTree::Length{arg, line: *line, col: *col}),
+        ParseTree::Encapsulate{arg, line, col} => unary(expression, arg, |arg| ParseTree::Encapsulate{arg, line: *line, col: *col}),
+        ParseTree::Strict{arg, line, col} => unary(expression, arg, |arg| ParseTree::Strict{arg, line: *line, col: *col}),
+        ParseTree::Debug{arg, line, col} => unary(expression, arg, |arg| ParseTree::Debug{arg, line: *line, col: *col}),
+        ParseTree::Shared{arg, slot} => unary(expression, arg, |arg| ParseTree::Shared{arg, slot: *slot}),
+
+        ParseTree::Addition{arg1, arg2, line, col} =>
+            binary(expression, arg1, arg2, |arg1, arg2| ParseTree::Addition{arg1, arg2, line: *line, col: *col}),
+        ParseTree::IndexSubtraction{arg1, arg2, line, col} =>
+            binary(expression, arg1, arg2, |arg1, arg2| ParseTree::IndexSubtraction{arg1, arg2, line: *line, col: *col}),
+        ParseTree::Induction{arg1, arg2, line, col} =>
+            binary(expression, arg1, arg2, |arg1, arg2| ParseTree::Induction{arg1, arg2, line: *line, col: *col}),
+        ParseTree::Map{arg1, arg2, line, col} =>
+            binary(expression, arg1, arg2, |arg1, arg2| ParseTree::Map{arg1, arg2, line: *line, col: *col}),
+    }
+}
+
+// rebuilds a node with optimized children, reusing it if nothing changed
+fn unary(
+    expression: &Rc<ParseTree>, arg: &Rc<ParseTree>,
+    build: impl Fn(Rc<ParseTree>) -> ParseTree
+) -> Rc<ParseTree> {
+    let newarg = optimize(arg);
+    if Rc::ptr_eq(&newarg, arg) {
+        expression.clone()
+    } else {
+        Rc::new(build(newarg))
+    }
+}
+
+fn binary(
+    expression: &Rc<ParseTree>, arg1: &Rc<ParseTree>, arg2: &Rc<ParseTree>,
+    build: impl Fn(Rc<ParseTree>, Rc<ParseTree>) -> ParseTree
+) -> Rc<ParseTree> {
+    let newarg1 = optimize(arg1);
+    let newarg2 = optimize(arg2);
+    if Rc::ptr_eq(&newarg1, arg1) && Rc::ptr_eq(&newarg2, arg2) {
+        expression.clone()
+    } else {
+        Rc::new(build(newarg1, newarg2))
+    }
+}
+
+// returns a replacement for a node whose children are already optimized
+fn simplify(expression: &Rc<ParseTree>) -> Option<Rc<ParseTree>> {
+    let (line, col) = expression.position();
+    let number = |n| Some(Rc::new(ParseTree::Number{n, line, col}));
+    match &**expression {
+        ParseTree::Length{arg, ..} => match &**arg {
+            ParseTree::Number{n, ..} => number(n.abs()),
+            _ => number(list_literal(arg)?.len() as i64),
+        },
+
+        ParseTree::Addition{arg1, arg2, ..} => match (&**arg1, &**arg2) {
+            (ParseTree::Number{n: n1, ..}, ParseTree::Number{n: n2, ..}) => number(n1.checked_add(*n2)?),
+            _ => None,
+        },
+
+        ParseTree::IndexSubtraction{arg1, arg2, ..} => {
+            let k = match &**arg2 {
+                ParseTree::Number{n, ..} => *n,
+                _ => return None,
+            };
+            match &**arg1 {
+                ParseTree::Number{n, ..} => number(n.checked_sub(k)?),
+
+                // the induction evaluates X right away, so it can only be dropped if it is a constant
+                ParseTree::Induction{arg1: x, arg2: y, ..} => match (&**x, k) {
+                    (ParseTree::Number{..}, 0) => Some(x.clone()),
+                    // Y is evaluated k times, so its debug operators would print k times
+                    (ParseTree::Number{..}, 1) if !y.uses_input() => Some(y.clone()),
+                    (ParseTree::Number{..}, 2..) if !y.uses_input() && !y.has_debug() => Some(y.clone()),
+                    _ => None,
+                },
+
+                // negative indexes are left alone, since concatenations don't count them from the end
+                _ => {
+                    let elements = list_literal(arg1)?;
+                    usize::try_from(k).ok()
+                        .and_then(|k| elements.get(k))
+                        .cloned()
+                }
+            }
+        }
+
+        _ => None,
+    }
+}
+
+// the (unevaluated) elements of lists like 1[](2[])(3[]), which are never evaluated by
+// indexing or measuring a different element
+fn list_literal(expression: &Rc<ParseTree>) -> Option<Vec<Rc<ParseTree>>> {
+    match &**expression {
+        ParseTree::EmptyList{..} => Some(Vec::new()),
+        ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} => Some(vec![arg.clone()]),
+        ParseTree::Addition{arg1, arg2, ..} => {
+            let mut elements = list_literal(arg1)?;
+            elements.extend(list_literal(arg2)?);
+            Some(elements)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsetree::parse;
+
+    fn optimized(expr: &str) -> ParseTree {
+        let pt = Rc::new(parse(expr).expect("parse error"));
+        (*optimize(&pt)).clone()
+    }
+
+    #[test]
+    fn constant_folding() {
+        assert_eq!(optimized("1(2)[5]"), ParseTree::Number{n: -2, line: 1, col: 5});
+        assert_eq!(optimized("1[](2[])(3[])()"), ParseTree::Number{n: 3, line: 1, col: 14});
+        assert_eq!(optimized("4[](5[])[1]"), ParseTree::Number{n: 5, line: 1, col: 5});
+        assert_eq!(optimized("1(0][()()]"), parse("1(0][()()]").expect("parse error"));
+        assert_eq!(optimized("1(0][2]"), ParseTree::Number{n: 0, line: 1, col: 3});
+    }
+
+    #[test]
+    fn simplifications() {
+        // the encapsulated element is kept as is, debug operator included
+        assert_eq!(optimized("()![][0]"), parse("()!").expect("parse error"));
+        assert_eq!(optimized("5(3)[3]"), ParseTree::Number{n: 5, line: 1, col: 5});
+        // [] decides what negative and out of bounds indexes reach, and a(k) may overflow
+        for expr in ["()[]([])", "[](()[])", "()()(5)[5]", "9223372036854775807(1)[1]"] {
+            assert_eq!(optimized(expr), parse(expr).expect("parse error"), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn errors_are_kept() {
+        // these all fail at runtime, so they can't be simplified away
+        for expr in ["[](())", "()(5)[5]", "1[](2[])[5]", "1!(3)", "()(0][1]"] {
+            assert_eq!(optimized(expr), parse(expr).expect("parse error"), "{}", expr);
+        }
+    }
+
+    // every example, and these programs, should behave the same with and without optimization
+    #[test]
+    fn optimized_examples() {
+        let programs = ["[](0(()])[0[1]]", "0(()]([])[5]", "[](5[])[0[1]]", "5[]([])[3]"];
+        crate::differential::behaves_the_same(&programs, optimize, false);
+    }
+}
@@ -0,0 +1,249 @@
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+
+use super::errors::RuntimeError;
+use super::value::{Value, INFINITE_LIST_PREVIEW_LENGTH};
+
+// Where the output of debug operators goes. Normally it is written to stderr (or the file
+// given with --debug-out) right away, but while a closure is run through capture it is kept
+// instead, so that elements forced on other threads can be printed in the order they would
+// have been forced in on one thread.
+thread_local! {
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+    static SETTINGS: Cell<DebugSettings> = const { Cell::new(DebugSettings::DEFAULT) };
+    // None for stderr
+    static SINK: RefCell<Option<Box<dyn Write>>> = const { RefCell::new(None) };
+    // how many elements of lazy lists are being computed, which is how deep into function
+    // bodies evaluation is
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugFormat {
+    // Debug at 1:2 - [1, 2]
+    Text,
+    // {"line":1,"col":2,"depth":0,"value":[1, 2]}
+    Json,
+}
+
+// How debug operators print. Threads forcing elements for --jobs are given the same settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugSettings {
+    // false makes ! only pass its value on
+    pub enabled: bool,
+    pub format: DebugFormat,
+    // how many list elements one debug print writes out in total before leaving the rest as ...
+    pub limit: usize,
+}
+
+impl DebugSettings {
+    pub const DEFAULT: DebugSettings = DebugSettings {
+        enabled: true,
+        format: DebugFormat::Text,
+        limit: 1000,
+    };
+}
+
+impl Default for DebugSettings {
+    fn default() -> Self {
+        DebugSettings::DEFAULT
+    }
+}
+
+pub fn configure(settings: DebugSettings) {
+    SETTINGS.with(|s| s.set(settings));
+}
+
+pub fn settings() -> DebugSettings {
+    SETTINGS.with(Cell::get)
+}
+
+pub fn enabled() -> bool {
+    settings().enabled
+}
+
+// sends debug output to out instead of stderr
+pub fn set_sink(out: Box<dyn Write>) {
+    SINK.with(|s| *s.borrow_mut() = Some(out));
+}
+
+// writes out anything the sink is holding on to, and goes back to stderr
+pub fn finish() -> io::Result<()> {
+    match SINK.with(|s| s.borrow_mut().take()) {
+        Some(mut out) => out.flush(),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn depth() -> usize {
+    DEPTH.with(Cell::get)
+}
+
+pub(crate) fn set_depth(depth: usize) {
+    DEPTH.with(|d| d.set(depth));
+}
+
+// runs f one function body deeper
+pub(crate) fn nested<T>(f: impl FnOnce() -> T) -> T {
+    let depth = depth();
+    set_depth(depth + 1);
+    let result = f();
+    set_depth(depth);
+    result
+}
+
+pub fn debug(line: u32, col: u32, value: &Value) {
+    let settings = settings();
+    let mut budget = settings.limit;
+    let mut rendered = String::new();
+    let result = render(value, &mut budget, &mut rendered);
+    let text = match (settings.format, result) {
+        (DebugFormat::Text, Ok(_)) => format!("Debug at {}:{} - {}\n", line, col, rendered),
+        (DebugFormat::Text, Err(e)) => format!("Debug at {}:{} - {:?}\n", line, col, e),
+        (DebugFormat::Json, Ok(truncated)) => format!(
+            "{{\"line\":{},\"col\":{},\"depth\":{},\"value\":{}{}}}\n",
+            line, col, depth(), json_value(&rendered), if truncated { ",\"truncated\":true" } else { "" }
+        ),
+        (DebugFormat::Json, Err(e)) => format!(
+            "{{\"line\":{},\"col\":{},\"depth\":{},\"code\":\"{}\",\"error\":{}}}\n",
+            line, col, depth(), e.code(), quote(&e.to_string())
+        ),
+    };
+    write(&text);
+}
+
+// Writes value the way it is shown in the output, with at most budget list elements in
+// total. Returns whether anything was left out, which infinite lists always are.
+fn render(value: &Value, budget: &mut usize, s: &mut String) -> Result<bool, RuntimeError> {
+    let l = match value {
+        Value::Number(n) => {
+            s.push_str(&n.to_string());
+            return Ok(false);
+        }
+        Value::List(l) => l,
+    };
+    let (len, mut cut) = match l.length() {
+        Err(RuntimeError::ResolvingInfiniteList(..)) => (INFINITE_LIST_PREVIEW_LENGTH, true),
+        Ok(len) => (len, false),
+        Err(e) => return Err(e),
+    };
+    let mut truncated = false;
+    s.push('[');
+    for i in 0..len {
+        if *budget == 0 {
+            cut = true;
+            break;
+        }
+        *budget -= 1;
+        if i > 0 {
+            s.push_str(", ");
+        }
+        truncated |= render(&l.index(i)?, budget, s)?;
+    }
+    if cut {
+        s.push_str(if s.ends_with('[') { "..." } else { ", ..." });
+    }
+    s.push(']');
+    Ok(truncated || cut)
+}
+
+// a rendered value as JSON, which only needs the ...s taken out
+fn json_value(rendered: &str) -> String {
+    rendered.replace(", ...", "").replace("...", "")
+}
+
+// a string as JSON
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// writes text to the debug output, or keeps it if it is being captured
+pub fn write(text: &str) {
+    let captured = CAPTURED.with(|c| match &mut *c.borrow_mut() {
+        Some(captured) => {
+            captured.push_str(text);
+            true
+        }
+        None => false,
+    });
+    if captured {
+        return;
+    }
+    // a debug print that can't be written isn't worth stopping the program over
+    SINK.with(|s| match &mut *s.borrow_mut() {
+        Some(out) => {
+            let _ = out.write_all(text.as_bytes());
+        }
+        None => eprint!("{}", text),
+    });
+}
+
+// runs f, returning what it would have printed instead of printing it
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let outer = CAPTURED.with(|c| c.replace(Some(String::new())));
+    let result = f();
+    let captured = CAPTURED.with(|c| c.replace(outer)).unwrap_or_default();
+    (result, captured)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+
+    #[test]
+    fn nested_captures() {
+        let ((inner, ()), outer) = capture(|| {
+            write("a");
+            let inner = capture(|| debug(1, 2, &Value::Number(3)));
+            write("b");
+            (inner.1, ())
+        });
+        assert_eq!(inner, "Debug at 1:2 - 3\n");
+        assert_eq!(outer, "ab");
+    }
+
+    fn debugged(expr: &str, settings: DebugSettings) -> String {
+        let pt = parse(expr).expect("parse error");
+        configure(settings);
+        let (_, printed) = capture(|| evaluate(&pt, &Value::Number(0)).and_then(|v| v.force_resolve()));
+        configure(DebugSettings::DEFAULT);
+        printed
+    }
+
+    #[test]
+    fn limits() {
+        let limited = DebugSettings { limit: 4, ..DebugSettings::DEFAULT };
+        assert_eq!(debugged("1[](2[])(3[])!", limited), "Debug at 1:14 - [1, 2, 3]\n");
+        assert_eq!(debugged("1[](2[])(3[])[]!", limited), "Debug at 1:16 - [[1, 2, 3]]\n");
+        assert_eq!(debugged("1[](2[])(3[])[](4[])(5[])!", limited), "Debug at 1:26 - [[1, 2, 3], ...]\n");
+        assert_eq!(debugged("0(()(1)]![0]", limited), "Debug at 1:9 - [0, 1, 2, ...]\n");
+        assert_eq!(debugged("0(()(1)][]![0]", DebugSettings { limit: 1, ..limited }), "Debug at 1:11 - [[...]]\n");
+        assert_eq!(debugged("1!", DebugSettings { enabled: false, ..limited }), "");
+    }
+
+    #[test]
+    fn json() {
+        let json = DebugSettings { format: DebugFormat::Json, limit: 3, ..DebugSettings::DEFAULT };
+        assert_eq!(debugged("1[](2[])(3[])!", json), "{\"line\":1,\"col\":14,\"depth\":0,\"value\":[1, 2, 3]}\n");
+        assert_eq!(debugged("1[](2[])(3[])(4[])!", json), "{\"line\":1,\"col\":19,\"depth\":0,\"value\":[1, 2, 3],\"truncated\":true}\n");
+        // printed while computing an element of the map
+        assert_eq!(debugged("1[][()!)", json), "{\"line\":1,\"col\":7,\"depth\":1,\"value\":1}\n");
+        assert_eq!(
+            debugged("1[][()(1[]))!", json),
+            "{\"line\":1,\"col\":13,\"depth\":0,\"code\":\"LM0105\",\"error\":\"Mismatched Types - Cannot add number and list (line 1)\"}\n"
+        );
+    }
+}
@@ -1,65 +1,257 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 
 use super::errors::ParseError;
+use super::sync::Rc;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParseTree {
-    Number{n: i64, line: u32},
-    Input{line: u32},
-    EmptyList{line: u32},
-    Length{arg: Box<ParseTree>, line: u32},
-    Encapsulate{arg: Box<ParseTree>, line: u32},
-    Addition{arg1: Box<ParseTree>, arg2: Box<ParseTree>, line: u32},
-    IndexSubtraction{arg1: Box<ParseTree>, arg2: Box<ParseTree>, line: u32},
-    Induction{arg1: Box<ParseTree>, arg2: Box<ParseTree>, line: u32},
-    Map{arg1: Box<ParseTree>, arg2: Box<ParseTree>, line: u32},
-    Debug{arg: Box<ParseTree>, line: u32, col: u32},
+    Number{n: i64, line: u32, col: u32},
+    Input{line: u32, col: u32},
+    EmptyList{line: u32, col: u32},
+    Length{arg: Rc<ParseTree>, line: u32, col: u32},
+    Encapsulate{arg: Rc<ParseTree>, line: u32, col: u32},
+    Addition{arg1: Rc<ParseTree>, arg2: Rc<ParseTree>, line: u32, col: u32},
+    IndexSubtraction{arg1: Rc<ParseTree>, arg2: Rc<ParseTree>, line: u32, col: u32},
+    Induction{arg1: Rc<ParseTree>, arg2: Rc<ParseTree>, line: u32, col: u32},
+    Map{arg1: Rc<ParseTree>, arg2: Rc<ParseTree>, line: u32, col: u32},
+    Debug{arg: Rc<ParseTree>, line: u32, col: u32},
+    // not produced by the parser, see cse::share
+    Shared{arg: Rc<ParseTree>, slot: usize},
+    // X[] with X evaluated right away instead of when it is first needed, see strictness::mark
+    Strict{arg: Rc<ParseTree>, line: u32, col: u32},
+}
+
+// Every node starts at a different character of the source, so its position identifies it.
+// This stays the same between runs of a program, unlike the addresses of the shared nodes.
+pub type NodeId = (u32, u32);
+
+// where a node's code starts and ends, as line, col, line, col
+pub type Span = (u32, u32, u32, u32);
+
+// Children are shared so that lazy lists can keep a reference to a function body
+// instead of copying it.
+impl ParseTree {
+    pub fn id(&self) -> NodeId {
+        self.position()
+    }
+
+    // the line and column of the bracket (or digit, or '!') that starts this node
+    pub fn position(&self) -> (u32, u32) {
+        match self {
+            ParseTree::Number{line, col, ..} |
+            ParseTree::Input{line, col} |
+            ParseTree::EmptyList{line, col} |
+            ParseTree::Length{line, col, ..} |
+            ParseTree::Encapsulate{line, col, ..} |
+            ParseTree::Strict{line, col, ..} |
+            ParseTree::Addition{line, col, ..} |
+            ParseTree::IndexSubtraction{line, col, ..} |
+            ParseTree::Induction{line, col, ..} |
+            ParseTree::Map{line, col, ..} |
+            ParseTree::Debug{line, col, ..} => (*line, *col),
+            ParseTree::Shared{arg, ..} => arg.position(),
+        }
+    }
+
+    // the operator of the node, with X and Y standing in for its children
+    pub fn form(&self) -> &'static str {
+        match self {
+            ParseTree::Number{..} => "number",
+            ParseTree::Input{..} => "()",
+            ParseTree::EmptyList{..} => "[]",
+            ParseTree::Length{..} => "X()",
+            ParseTree::Encapsulate{..} | ParseTree::Strict{..} => "X[]",
+            ParseTree::Addition{..} => "X(Y)",
+            ParseTree::IndexSubtraction{..} => "X[Y]",
+            ParseTree::Induction{..} => "X(Y]",
+            ParseTree::Map{..} => "X[Y)",
+            ParseTree::Debug{..} => "X!",
+            ParseTree::Shared{arg, ..} => arg.form(),
+        }
+    }
+
+    // whether () appears in the expression, not counting nested function bodies (which have their own ())
+    pub fn uses_input(&self) -> bool {
+        match self {
+            ParseTree::Input{..} => true,
+            ParseTree::Number{..} | ParseTree::EmptyList{..} => false,
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} |
+            ParseTree::Debug{arg, ..} | ParseTree::Shared{arg, ..} => arg.uses_input(),
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} =>
+                arg1.uses_input() || arg2.uses_input(),
+            ParseTree::Induction{arg1, ..} | ParseTree::Map{arg1, ..} => arg1.uses_input(),
+        }
+    }
+
+    // whether a debug operator appears anywhere in the expression, including function bodies
+    pub fn has_debug(&self) -> bool {
+        match self {
+            ParseTree::Debug{..} => true,
+            ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => false,
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} |
+            ParseTree::Strict{arg, ..} | ParseTree::Shared{arg, ..} => arg.has_debug(),
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => arg1.has_debug() || arg2.has_debug(),
+        }
+    }
+
+    // whether the expression is a list whenever it evaluates without an error
+    pub fn is_list(&self) -> bool {
+        match self {
+            ParseTree::EmptyList{..} | ParseTree::Encapsulate{..} | ParseTree::Strict{..} |
+            ParseTree::Induction{..} | ParseTree::Map{..} => true,
+            ParseTree::Addition{arg1, ..} | ParseTree::Debug{arg: arg1, ..} |
+            ParseTree::Shared{arg: arg1, ..} => arg1.is_list(),
+            _ => false,
+        }
+    }
+
+    // whether the expression is a number whenever it evaluates without an error
+    pub fn is_number(&self) -> bool {
+        match self {
+            ParseTree::Number{..} | ParseTree::Length{..} => true,
+            ParseTree::Addition{arg1, ..} | ParseTree::IndexSubtraction{arg1, ..} |
+            ParseTree::Debug{arg: arg1, ..} | ParseTree::Shared{arg: arg1, ..} => arg1.is_number(),
+            _ => false,
+        }
+    }
+}
+
+// Writes the expression back out as code, without comments or whitespace.
+// Structurally identical expressions are written the same way.
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseTree::Number{n, ..} => write!(f, "{}", n),
+            ParseTree::Input{..} => write!(f, "()"),
+            ParseTree::EmptyList{..} => write!(f, "[]"),
+            ParseTree::Length{arg, ..} => write!(f, "{}()", arg),
+            ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} => write!(f, "{}[]", arg),
+            ParseTree::Addition{arg1, arg2, ..} => write!(f, "{}({})", arg1, arg2),
+            ParseTree::IndexSubtraction{arg1, arg2, ..} => write!(f, "{}[{}]", arg1, arg2),
+            ParseTree::Induction{arg1, arg2, ..} => write!(f, "{}({}]", arg1, arg2),
+            ParseTree::Map{arg1, arg2, ..} => write!(f, "{}[{})", arg1, arg2),
+            ParseTree::Debug{arg, ..} => write!(f, "{}!", arg),
+            ParseTree::Shared{arg, ..} => write!(f, "{}", arg),
+        }
+    }
+}
+
+// Where each node of the program starts and ends in the source. A node starts where its
+// leftmost child does, and ends at its closing bracket, its last digit or its !.
+pub fn spans(program: &ParseTree, source: &str) -> HashMap<NodeId, Span> {
+    let mut parser = Parser::new(source);
+    let _ = parser.parse();
+    let closers = parser.closers();
+    let lines: Vec<Vec<char>> = source.lines().map(|l| l.chars().collect()).collect();
+
+    fn walk(
+        node: &ParseTree, closers: &HashMap<NodeId, NodeId>, lines: &[Vec<char>],
+        spans: &mut HashMap<NodeId, Span>
+    ) -> Span {
+        let (line, col) = node.id();
+        let (start, end) = match node {
+            ParseTree::Number{..} => {
+                let digits = lines.get(line as usize - 1).map_or(1, |l| {
+                    l.iter().skip(col as usize - 1).take_while(|c| c.is_ascii_digit()).count()
+                });
+                ((line, col), (line, col + digits.max(1) as u32 - 1))
+            }
+            ParseTree::Input{..} | ParseTree::EmptyList{..} => ((line, col), closers.get(&(line, col)).copied().unwrap_or((line, col))),
+            ParseTree::Shared{arg, ..} => return walk(arg, closers, lines, spans),
+            ParseTree::Debug{arg, ..} => {
+                let span = walk(arg, closers, lines, spans);
+                ((span.0, span.1), (line, col))
+            }
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} => {
+                let span = walk(arg, closers, lines, spans);
+                ((span.0, span.1), closers.get(&(line, col)).copied().unwrap_or((line, col)))
+            }
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => {
+                let span = walk(arg1, closers, lines, spans);
+                walk(arg2, closers, lines, spans);
+                ((span.0, span.1), closers.get(&(line, col)).copied().unwrap_or((line, col)))
+            }
+        };
+        let span = (start.0, start.1, end.0, end.1);
+        spans.insert((line, col), span);
+        span
+    }
+
+    let mut spans = HashMap::new();
+    walk(program, &closers, &lines, &mut spans);
+    spans
 }
 
 pub struct Parser {
-    s: String,
+    chars: Vec<char>,
     char_i: usize,
     linenum: u32,
-    colnum: u32
+    colnum: u32,
+    errors: Vec<ParseError>,
+    // (opener line, opener col, closer line, closer col, closer) for every matched bracket pair
+    pairs: Vec<(u32, u32, u32, u32, char)>,
 }
 
+// returns only the first error, see parse_all to get all of them
 pub fn parse(expr: &str) -> Result<ParseTree, ParseError> {
+    parse_all(expr).map_err(|mut errors| errors.remove(0))
+}
+
+pub fn parse_all(expr: &str) -> Result<ParseTree, Vec<ParseError>> {
     let mut parser = Parser::new(expr);
     parser.parse()
-        .and_then(|pt| pt.ok_or(ParseError::EmptyFile))
+        .and_then(|pt| pt.ok_or(vec![ParseError::EmptyFile]))
 }
 
 impl Parser {
     pub fn new(s: &str) -> Parser{
         Parser{
-            s: s.to_owned(),
+            chars: s.chars().collect(),
             char_i: 0,
             linenum: 1,
-            colnum: 1
+            colnum: 1,
+            errors: Vec::new(),
+            pairs: Vec::new(),
         }
     }
-    // if called with char_i right after an open bracket:
+
+    // where each bracket pair parsed so far closes, by where it opens
+    pub fn closers(&self) -> HashMap<(u32, u32), (u32, u32)> {
+        self.pairs.iter()
+            .map(|(ol, oc, cl, cc, _)| ((*ol, *oc), (*cl, *cc)))
+            .collect()
+    }
+
+    // parses the whole string, recovering from errors so that all of them can be reported at once
+    pub fn parse(&mut self) -> Result<Option<ParseTree>, Vec<ParseError>> {
+        let ans = self.parse_expression(false);
+        if self.errors.is_empty() {
+            Ok(ans)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // if called with char_i right after an open bracket (and in_brackets set):
     // returns the relevant parsetree
-    // sets char_i to be the index of the end bracket
+    // sets char_i to be the index of the end bracket, or past the end of the string if there isn't one
     // updates linenum and colnum to be accurate with char_i
-    pub fn parse(&mut self) -> Result<Option<ParseTree>, ParseError> {
+    // at the top level, close brackets are unmatched and are skipped
+    fn parse_expression(&mut self, in_brackets: bool) -> Option<ParseTree> {
         let mut ans: Option<ParseTree> = None;
         let mut numberstart = 0;
         let mut incomment = false;
         let mut innumber = false;
         loop {
-            if let Some(c) = self.s.chars().nth(self.char_i) {
+            if let Some(&c) = self.chars.get(self.char_i) {
                 // Comment handling
                 if c == '#' && !incomment {
                     if innumber {
-                        if let Some(_) = ans {
-                            return Err(ParseError::SyntaxError(format!(
-                                "Found number not leading expression at {}:{}",
-                                self.linenum,
-                                self.colnum
-                            )));
-                        }
-                        ans = self.parse_number(numberstart, self.char_i)?;
+                        ans = self.finish_number(ans, numberstart);
                         innumber = false;
                     }
 
@@ -74,15 +266,29 @@ impl Parser {
                     continue;
                 }
 
-                // Invalid Chars
+                // Invalid Chars are reported and then ignored
                 if !char::is_whitespace(c) {
                     match c {
                         '0'..='9' | '(' | ')' | '[' | ']' | '!' => (),
                         _ => {
-                            return Err(ParseError::InvalidCharacter(format!(
-                                "found invalid character \'{}\' at {}:{}",
-                                c, self.linenum, self.colnum
+                            // a run like `a + b` or `some text` is reported once, and
+                            // parsing picks up again at the next valid character
+                            let run = self.invalid_run();
+                            let help = self.invalid_character_help(&run)
+                                .map(|h| format!(" - help: {}", h))
+                                .unwrap_or_default();
+                            let text: String = run.iter().collect();
+                            self.errors.push(ParseError::InvalidCharacter(format!(
+                                "found invalid character{} \'{}\' at {}:{}{}",
+                                if run.len() > 1 { "s" } else { "" }, text, self.linenum, self.colnum, help
                             )));
+                            if innumber {
+                                ans = self.finish_number(ans, numberstart);
+                                innumber = false;
+                            }
+                            self.char_i += run.len();
+                            self.colnum += run.len() as u32;
+                            continue;
                         }
                     }
                 }
@@ -97,23 +303,17 @@ impl Parser {
                     }
                     _ => {
                         if innumber {
-                            if let Some(_) = ans {
-                                return Err(ParseError::SyntaxError(format!(
-                                    "Found number not leading expression at {}:{}",
-                                    self.linenum,
-                                    self.colnum
-                                )));
-                            }
-                            ans = self.parse_number(numberstart, self.char_i)?;
+                            ans = self.finish_number(ans, numberstart);
                             innumber = false;
                         }
                     }
                 }
 
                 // line numbers:
+                // colnum is incremented at the end of the loop, so this puts the next char at column 1
                 if c == '\n'{
                     self.linenum += 1;
-                    self.colnum = 1;
+                    self.colnum = 0;
                 }
 
                 // Bracket handling
@@ -123,50 +323,30 @@ impl Parser {
                         let old_colnum = self.colnum;
                         self.char_i += 1;
                         self.colnum += 1;
-                        let rec = self.parse()?;
-                        if let Some(endchar) = self.s.chars().nth(self.char_i) {
-                            ans = match ans {
-                                None => match (c, endchar, rec) {
-                                    ('(', ')', None) => Some(ParseTree::Input{line: old_linenum}),
-                                    ('[', ']', None) => Some(ParseTree::EmptyList{line: old_linenum}),
-                                    _ => {
-                                        return Err(ParseError::SyntaxError(format!(
-                                            "Invalid expression with no predecessor: \"{}{}\" at {}:{}",
-                                            c, endchar,
-                                            old_linenum, old_colnum
-                                        )));
-                                    }
-                                },
-                                Some(prevpt) => match (c, endchar, rec) {
-                                    ('(', ')', None) => Some(ParseTree::Length{
-                                        arg: Box::new(prevpt), line: old_linenum}),
-                                    ('[', ']', None) => Some(ParseTree::Encapsulate{
-                                        arg: Box::new(prevpt), line: old_linenum}),
-                                    ('(', ')', Some(pt)) => Some(ParseTree::Addition{
-                                            arg1: Box::new(prevpt), arg2: Box::new(pt), line: old_linenum}),
-                                    ('[', ']', Some(pt)) => Some(ParseTree::IndexSubtraction{
-                                            arg1: Box::new(prevpt), arg2: Box::new(pt), line: old_linenum}),
-                                    ('(', ']', Some(pt)) => Some(ParseTree::Induction{
-                                            arg1: Box::new(prevpt), arg2: Box::new(pt), line: old_linenum}),
-                                    ('[', ')', Some(pt)) => Some(ParseTree::Map{
-                                            arg1: Box::new(prevpt), arg2: Box::new(pt), line: old_linenum}),
-                                    _ => {
-                                        return Err(ParseError::SyntaxError(format!(
-                                            "Invalid expression \"{}...{}\" at {}:{}",
-                                            c, endchar,
-                                            old_linenum, old_colnum
-                                        )));
-                                    }
-                                },
-                            };
-                        } else {
-                            return Err(ParseError::UnexpectedEOF);
-                        }
+                        let rec = self.parse_expression(true);
+                        let endchar = match self.chars.get(self.char_i) {
+                            Some(&endchar) => {
+                                self.pairs.push((old_linenum, old_colnum, self.linenum, self.colnum, endchar));
+                                endchar
+                            }
+                            None => {
+                                // act as if the matching close bracket was at the end of the file
+                                self.errors.push(self.unmatched_bracket(c, old_linenum, old_colnum));
+                                if c == '(' { ')' } else { ']' }
+                            }
+                        };
+                        ans = self.combine(ans, rec, c, endchar, old_linenum, old_colnum);
                     }
                     ')' | ']' => {
                         // most close brackets/parens should be consumed by the recursive calls,
                         // so the first one we see is the end of the expression
-                        return Ok(ans);
+                        if in_brackets {
+                            return ans;
+                        }
+                        self.errors.push(ParseError::UnmatchedBracket(format!(
+                            "found unmatched \'{}\' at {}:{}",
+                            c, self.linenum, self.colnum
+                        )));
                     }
                     _ => (),
                 }
@@ -175,10 +355,10 @@ impl Parser {
                 if c == '!' {
                     if let Some(prevpt) = ans {
                         ans = Some(ParseTree::Debug{
-                            arg: Box::new(prevpt), line: self.linenum, col: self.colnum
+                            arg: Rc::new(prevpt), line: self.linenum, col: self.colnum
                         })
                     }else{
-                        return Err(ParseError::SyntaxError(format!(
+                        self.errors.push(ParseError::SyntaxError(format!(
                             "Invalid expression with no predecessor: \"!\" at {}:{}",
                             self.linenum, self.colnum
                         )));
@@ -187,38 +367,187 @@ impl Parser {
             } else {
                 // the other return case doesn't need this because the non-digit check already catches it
                 if innumber {
-                    if let Some(_) = ans {
-                        return Err(ParseError::SyntaxError(format!(
-                            "Found number not leading expression at {}:{}",
-                            self.linenum,
-                            self.colnum
-                        )));
-                    }
-                    ans = self.parse_number(numberstart, self.char_i)?;
+                    ans = self.finish_number(ans, numberstart);
                 }
 
-                return Ok(ans);
+                return ans;
             }
             self.char_i += 1;
             self.colnum += 1;
         }
     }
 
-    // end is noninclusive
-    fn parse_number(&self, start: usize, end: usize) -> Result<Option<ParseTree>, ParseError> {
-        if let Some(numberstr) = self.s.get(start..end) {
-            if let Ok(n) = i64::from_str(numberstr) {
-                Ok(Some(ParseTree::Number{n, line: self.linenum}))
-            } else {
-                let old_colnum = self.colnum as usize - (self.char_i - start);
-                Err(ParseError::NumberParseError(format!(
-                    "Failed to parse number at {}:{}", old_colnum, self.linenum
-                )))
+    // the invalid characters starting at char_i, along with any spaces between them on the same line
+    fn invalid_run(&self) -> Vec<char> {
+        let valid = |c: &char| matches!(c, '0'..='9' | '(' | ')' | '[' | ']' | '!' | '#' | '\n');
+        let mut run: Vec<char> = self.chars[self.char_i..].iter()
+            .take_while(|c| !valid(c))
+            .copied()
+            .collect();
+        while run.last().is_some_and(|c| c.is_whitespace()) {
+            run.pop();
+        }
+        run
+    }
+
+    // suggestions for the invalid run at char_i, based on what it was probably meant to be
+    fn invalid_character_help(&self, run: &[char]) -> Option<String> {
+        let end = self.char_i + run.len();
+        let digits_after: String = self.chars[end..].iter()
+            .skip_while(|c| **c == ' ' || **c == '\t')
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        let digits_before: String = self.chars[..self.char_i].iter()
+            .rev()
+            .skip_while(|c| **c == ' ' || **c == '\t')
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        // X+Y and X-Y, where X and Y are single words or numbers
+        if let Some(op) = run.iter().position(|c| *c == '+' || *c == '-') {
+            let operand = |chars: &[char], digits: &String| {
+                let word: String = chars.iter().collect::<String>().trim().to_owned();
+                if word.is_empty() {
+                    Some(digits.clone())
+                } else if word.chars().all(char::is_alphanumeric) {
+                    Some(word)
+                } else {
+                    None
+                }
+            };
+            let (x, y) = (operand(&run[..op], &digits_before), operand(&run[op + 1..], &digits_after));
+            if let (Some(x), Some(y)) = (x, y) {
+                return Some(match (run[op], x.is_empty(), y.is_empty()) {
+                    ('-', true, false) if y.chars().all(|c| c.is_ascii_digit()) =>
+                        format!("negative literals don't exist, write 0[{}]", y),
+                    ('-', false, false) => format!("subtraction is written X[Y], use {}[{}]", x, y),
+                    ('-', _, _) => String::from("subtraction is written X[Y]"),
+                    (_, false, false) => format!("addition is written X(Y), use {}({})", x, y),
+                    _ => String::from("addition is written X(Y), use a(b) instead of a+b"),
+                });
+            }
+        }
+        match run[0] {
+            ',' => Some(String::from("there are no list literals, build lists like 1[](2[])(3[])")),
+            '{' | '}' | '<' | '>' => Some(String::from("only () and [] are brackets")),
+            c if c.is_alphabetic() => Some(String::from("did you forget `#` to start a comment?")),
+            _ => None,
+        }
+    }
+
+    // builds the node for a bracket pair following ans
+    // invalid combinations are reported and replaced with whatever is closest to valid
+    fn combine(
+        &mut self, ans: Option<ParseTree>, rec: Option<ParseTree>,
+        c: char, endchar: char, line: u32, col: u32
+    ) -> Option<ParseTree> {
+        match ans {
+            None => match (c, endchar, rec) {
+                ('(', ')', None) => Some(ParseTree::Input{line, col}),
+                ('[', ']', None) => Some(ParseTree::EmptyList{line, col}),
+                (_, _, rec) => {
+                    self.errors.push(ParseError::SyntaxError(format!(
+                        "Invalid expression with no predecessor: \"{}{}\" at {}:{}",
+                        c, endchar,
+                        line, col
+                    )));
+                    rec.or(Some(ParseTree::Input{line, col}))
+                }
+            },
+            Some(prevpt) => match (c, endchar, rec) {
+                ('(', ')', None) => Some(ParseTree::Length{
+                    arg: Rc::new(prevpt), line, col}),
+                ('[', ']', None) => Some(ParseTree::Encapsulate{
+                    arg: Rc::new(prevpt), line, col}),
+                ('(', ')', Some(pt)) => Some(ParseTree::Addition{
+                        arg1: Rc::new(prevpt), arg2: Rc::new(pt), line, col}),
+                ('[', ']', Some(pt)) => Some(ParseTree::IndexSubtraction{
+                        arg1: Rc::new(prevpt), arg2: Rc::new(pt), line, col}),
+                ('(', ']', Some(pt)) => Some(ParseTree::Induction{
+                        arg1: Rc::new(prevpt), arg2: Rc::new(pt), line, col}),
+                ('[', ')', Some(pt)) => Some(ParseTree::Map{
+                        arg1: Rc::new(prevpt), arg2: Rc::new(pt), line, col}),
+                _ => {
+                    self.errors.push(ParseError::SyntaxError(format!(
+                        "Invalid expression \"{}...{}\" at {}:{}",
+                        c, endchar,
+                        line, col
+                    )));
+                    Some(prevpt)
+                }
+            },
+        }
+    }
+
+    // called at the end of a number that started at start, with char_i just after it
+    fn finish_number(&mut self, ans: Option<ParseTree>, start: usize) -> Option<ParseTree> {
+        if ans.is_some() {
+            self.errors.push(ParseError::SyntaxError(format!(
+                "Found number not leading expression at {}:{}",
+                self.linenum,
+                self.colnum
+            )));
+            return ans;
+        }
+        let col = self.colnum - (self.char_i - start) as u32;
+        let numberstr: String = self.chars[start..self.char_i].iter().collect();
+        match i64::from_str(&numberstr) {
+            Ok(n) => Some(ParseTree::Number{n, line: self.linenum, col}),
+            Err(_) => {
+                self.errors.push(ParseError::NumberParseError(format!(
+                    "Failed to parse number at {}:{}", self.linenum, col
+                )));
+                Some(ParseTree::Number{n: 0, line: self.linenum, col})
             }
-        } else {
-            Err(ParseError::UnexpectedEOF)
         }
     }
+
+    fn unmatched_bracket(&self, c: char, line: u32, col: u32) -> ParseError {
+        let lines: Vec<String> = self.chars.iter().collect::<String>()
+            .lines()
+            .map(|l| l.to_owned())
+            .collect();
+        let indentation = |l: u32| lines.get(l as usize - 1)
+            .map(|s| s.chars().take_while(|c| c.is_whitespace()).count())
+            .unwrap_or(0);
+        let indent = indentation(line);
+
+        // a later close bracket that lines up with this one was probably meant for it,
+        // which means the bracket it actually closed is the one missing a closer
+        let misaligned = self.pairs.iter()
+            .filter(|(ol, oc, cl, cc, _)| {
+                (*ol, *oc) > (line, col) && cl > ol
+                    && *cc as usize == indentation(*cl) + 1
+                    && indentation(*cl) == indent
+                    && indentation(*ol) > indent
+            })
+            .min_by_key(|(ol, oc, _, _, _)| (*ol, *oc));
+        if let Some((ol, oc, cl, cc, closer)) = misaligned {
+            return ParseError::UnmatchedBracket(format!(
+                "unmatched \'{}\' at {}:{} - the \'{}\' at {}:{} lines up with it, so the bracket at {}:{} is probably missing its closer",
+                c, line, col, closer, cl, cc, ol, oc
+            ));
+        }
+
+        // otherwise, the closer probably goes at the end of the block indented further than this line
+        let mut lastline = line;
+        for l in line + 1..=lines.len() as u32 {
+            let trimmed = lines[l as usize - 1].trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if indentation(l) <= indent {
+                break;
+            }
+            lastline = l;
+        }
+        ParseError::UnmatchedBracket(format!(
+            "unmatched \'{}\' at {}:{} - its closer probably belongs at the end of line {}",
+            c, line, col, lastline
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -228,19 +557,19 @@ mod tests {
     #[test]
     fn nonary_operations() {
         let a = parse("0").expect("failed to parse");
-        assert_eq!(a, ParseTree::Number{n: 0, line: 1});
+        assert_eq!(a, ParseTree::Number{n: 0, line: 1, col: 1});
         let a = parse("()").expect("failed to parse");
-        assert_eq!(a, ParseTree::Input{line: 1});
+        assert_eq!(a, ParseTree::Input{line: 1, col: 1});
         let a = parse("[]").expect("failed to parse");
-        assert_eq!(a, ParseTree::EmptyList{line: 1});
+        assert_eq!(a, ParseTree::EmptyList{line: 1, col: 1});
     }
 
     #[test]
     fn unary_operations() {
         let a = parse("0()").expect("failed to parse");
-        assert_eq!(a, ParseTree::Length{arg: Box::new(ParseTree::Number{n:0, line: 1}), line: 1});
+        assert_eq!(a, ParseTree::Length{arg: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}), line: 1, col: 2});
         let a = parse("0[]").expect("failed to parse");
-        assert_eq!(a, ParseTree::Encapsulate{arg: Box::new(ParseTree::Number{n:0, line: 1}), line: 1});
+        assert_eq!(a, ParseTree::Encapsulate{arg: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}), line: 1, col: 2});
     }
 
     #[test]
@@ -249,36 +578,40 @@ mod tests {
         assert_eq!(
             a,
             ParseTree::Addition{
-                arg1: Box::new(ParseTree::Number{n:0, line: 1}),
-                arg2: Box::new(ParseTree::Number{n:0, line: 1}),
-                line: 1
+                arg1: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}),
+                arg2: Rc::new(ParseTree::Number{n:0, line: 1, col: 3}),
+                line: 1,
+                col: 2
             }
         );
         let a = parse("0[0]").expect("failed to parse");
         assert_eq!(
             a,
             ParseTree::IndexSubtraction{
-                arg1: Box::new(ParseTree::Number{n:0, line: 1}),
-                arg2: Box::new(ParseTree::Number{n:0, line: 1}),
-                line: 1
+                arg1: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}),
+                arg2: Rc::new(ParseTree::Number{n:0, line: 1, col: 3}),
+                line: 1,
+                col: 2
             }
         );
         let a = parse("0(0]").expect("failed to parse");
         assert_eq!(
             a,
             ParseTree::Induction{
-                arg1: Box::new(ParseTree::Number{n:0, line: 1}),
-                arg2: Box::new(ParseTree::Number{n:0, line: 1}),
-                line: 1
+                arg1: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}),
+                arg2: Rc::new(ParseTree::Number{n:0, line: 1, col: 3}),
+                line: 1,
+                col: 2
             }
         );
         let a = parse("0[0)").expect("failed to parse");
         assert_eq!(
             a,
             ParseTree::Map{
-                arg1: Box::new(ParseTree::Number{n:0, line: 1}),
-                arg2: Box::new(ParseTree::Number{n:0, line: 1}),
-                line: 1
+                arg1: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}),
+                arg2: Rc::new(ParseTree::Number{n:0, line: 1, col: 3}),
+                line: 1,
+                col: 2
             }
         );
     }
@@ -286,25 +619,31 @@ mod tests {
     #[test]
     fn whitespace_test() {
         let a = parse(" \t\n0\t\n ").expect("failed to parse");
-        assert_eq!(a, ParseTree::Number{n:0, line: 2});
+        assert_eq!(a, ParseTree::Number{n:0, line: 2, col: 1});
         let a = parse(" [ \t \n ] \t").expect("failed to parse");
-        assert_eq!(a, ParseTree::EmptyList{line: 1});
+        assert_eq!(a, ParseTree::EmptyList{line: 1, col: 2});
     }
 
     #[test]
     fn comment_test() {
         let a = parse("0#[]").expect("failed to parse");
-        assert_eq!(a, ParseTree::Number{n:0, line: 1});
+        assert_eq!(a, ParseTree::Number{n:0, line: 1, col: 1});
         let a = parse("(#[]\n)").expect("failed to parse");
-        assert_eq!(a, ParseTree::Input{line: 1});
+        assert_eq!(a, ParseTree::Input{line: 1, col: 1});
         let a = parse("123#456").expect("failed to parse");
-        assert_eq!(a, ParseTree::Number{n:123, line: 1});
+        assert_eq!(a, ParseTree::Number{n:123, line: 1, col: 1});
     }
 
     #[test]
     fn line_number_test() {
         let a = parse("\n#\n0").expect("failed to parse");
-        assert_eq!(a, ParseTree::Number{n:0, line: 3});
+        assert_eq!(a, ParseTree::Number{n:0, line: 3, col: 1});
+    }
+
+    #[test]
+    fn column_after_newline_test() {
+        let a = parse("[]\n!").expect("failed to parse");
+        assert_eq!(a, ParseTree::Debug{arg: Rc::new(ParseTree::EmptyList{line: 1, col: 1}), line: 2, col: 1});
     }
 
     #[test]
@@ -313,13 +652,15 @@ mod tests {
         assert_eq!(
             a,
             ParseTree::Addition{
-                arg1: Box::new(ParseTree::Addition{
-                    arg1: Box::new(ParseTree::Number{n:0, line: 1}),
-                    arg2: Box::new(ParseTree::Number{n:0, line: 3}),
-                    line: 2
+                arg1: Rc::new(ParseTree::Addition{
+                    arg1: Rc::new(ParseTree::Number{n:0, line: 1, col: 1}),
+                    arg2: Rc::new(ParseTree::Number{n:0, line: 3, col: 1}),
+                    line: 2,
+                    col: 1
                 }),
-                arg2: Box::new(ParseTree::Number{n:0, line: 6}),
-                line: 5
+                arg2: Rc::new(ParseTree::Number{n:0, line: 6, col: 1}),
+                line: 5,
+                col: 1
             }
         );
     }
@@ -330,15 +671,102 @@ mod tests {
         assert_eq!(
             a,
             ParseTree::Encapsulate {
-                arg: Box::new(ParseTree::Debug{
-                    arg: Box::new(ParseTree::EmptyList{
-                        line: 1
+                arg: Rc::new(ParseTree::Debug{
+                    arg: Rc::new(ParseTree::EmptyList{
+                        line: 1,
+                        col: 1
                     }),
                     line: 1,
                     col: 3
                 }),
-                line: 1
+                line: 1,
+                col: 4
             }
         );
     }
+
+    #[test]
+    fn multiple_errors() {
+        let errors = parse_all("1a(2)\n(b)").expect_err("expected errors");
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], ParseError::InvalidCharacter(_)));
+        assert!(matches!(errors[1], ParseError::InvalidCharacter(_)));
+
+        let errors = parse_all("(5)(!)").expect_err("expected errors");
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn unmatched_open_bracket() {
+        let e = parse("0(1").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Unmatched Bracket - unmatched '(' at 1:2 - its closer probably belongs at the end of line 1"
+        );
+
+        let e = parse("0(\n    1\n    (2)\n# end\n").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Unmatched Bracket - unmatched '(' at 1:2 - its closer probably belongs at the end of line 3"
+        );
+    }
+
+    #[test]
+    fn misaligned_close_bracket() {
+        let e = parse("0(\n    1[]\n    (2[]\n    (3[])\n)").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Unmatched Bracket - unmatched '(' at 1:2 - the ')' at 5:1 lines up with it, so the bracket at 3:5 is probably missing its closer"
+        );
+    }
+
+    #[test]
+    fn unmatched_close_bracket() {
+        let errors = parse_all("1)(2)").expect_err("expected errors");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], ParseError::UnmatchedBracket(_)));
+    }
+
+    #[test]
+    fn invalid_character_help() {
+        let e = parse("1(-5)").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Invalid Character Error - found invalid character '-' at 1:3 - help: negative literals don't exist, write 0[5]"
+        );
+        let e = parse("12+3").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Invalid Character Error - found invalid character '+' at 1:3 - help: addition is written X(Y), use 12(3)"
+        );
+        let e = parse("0{}").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Invalid Character Error - found invalid characters '{}' at 1:2 - help: only () and [] are brackets"
+        );
+        let e = parse("a+b").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Invalid Character Error - found invalid characters 'a+b' at 1:1 - help: addition is written X(Y), use a(b)"
+        );
+        let e = parse("x - 1").expect_err("expected error");
+        assert_eq!(
+            format!("{}", e),
+            "Invalid Character Error - found invalid characters 'x -' at 1:1 - help: subtraction is written X[Y], use x[1]"
+        );
+    }
+
+    #[test]
+    fn missing_comment_marker() {
+        // each run of text is reported once, and brackets after it are still parsed
+        let errors = parse_all("0[]\nmake a list (of stuff)\n(1[])").expect_err("expected errors");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            format!("{}", errors[0]),
+            "Invalid Character Error - found invalid characters 'make a list' at 2:1 - help: did you forget `#` to start a comment?"
+        );
+        let errors = parse_all("1(x)(2)").expect_err("expected errors");
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], ParseError::InvalidCharacter(_)));
+    }
 }
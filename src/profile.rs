@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::errors::RuntimeError;
+use super::observer::EvalObserver;
+use super::parsetree::{NodeId, ParseTree};
+use super::value::{Function, Value};
+
+// Where evaluation spends its time, for `--profile`. Only the tree walker reports to
+// observers, since it is the one that knows which node it is evaluating.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NodeProfile {
+    pub evaluations: u64,
+    // time spent evaluating the node, only counting the outermost evaluation when a node is
+    // evaluated again while it is being evaluated
+    pub inclusive: Duration,
+    // the time spent on the node itself, without any other nodes evaluated on the way,
+    // including the ones evaluated to produce elements it forced
+    pub exclusive: Duration,
+    // lazy lists (maps, inductions and X[]) made by the node
+    pub thunks: u64,
+    // times an element of one of the node's lazy lists was computed
+    pub forced: u64,
+}
+
+struct Frame {
+    node: NodeId,
+    start: Instant,
+    // time spent in nodes evaluated while this one was
+    children: Duration,
+    path: usize,
+    outermost: bool,
+}
+
+// Records a profile of everything evaluated while it is observing, see observer::observe.
+#[derive(Default)]
+pub struct Profiler {
+    nodes: HashMap<NodeId, NodeProfile>,
+    // how many times each function body was called, by the id of the body
+    body_calls: HashMap<NodeId, u64>,
+    stack: Vec<Frame>,
+    // how many frames of each node are on the stack
+    active: HashMap<NodeId, usize>,
+    // every stack of nodes seen so far, as the stack it extends and its top node
+    paths: Vec<(Option<usize>, NodeId)>,
+    path_ids: HashMap<(Option<usize>, NodeId), usize>,
+    // exclusive time spent with each stack
+    path_time: Vec<Duration>,
+}
+
+pub struct Profile {
+    pub nodes: HashMap<NodeId, NodeProfile>,
+    paths: Vec<(Option<usize>, NodeId)>,
+    path_time: Vec<Duration>,
+}
+
+impl Profiler {
+    // Returns what was recorded while evaluating expression, starting over afterwards. The
+    // elements forced by each node are the calls of its function body.
+    pub fn finish(&mut self, expression: &ParseTree) -> Profile {
+        let profiler = std::mem::take(self);
+        let mut nodes = profiler.nodes;
+        for (id, node) in index(expression) {
+            let body = match node {
+                ParseTree::Encapsulate{arg, ..} => arg,
+                ParseTree::Induction{arg2, ..} | ParseTree::Map{arg2, ..} => arg2,
+                _ => continue,
+            };
+            if let Some(calls) = profiler.body_calls.get(&body.id()) {
+                nodes.entry(id).or_default().forced += calls;
+            }
+        }
+        Profile {
+            nodes,
+            paths: profiler.paths,
+            path_time: profiler.path_time,
+        }
+    }
+
+    fn exit(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = frame.start.elapsed();
+        let exclusive = elapsed.saturating_sub(frame.children);
+        let node = self.nodes.entry(frame.node).or_default();
+        node.evaluations += 1;
+        node.exclusive += exclusive;
+        if frame.outermost {
+            node.inclusive += elapsed;
+        }
+        self.path_time[frame.path] += exclusive;
+        if let Some(active) = self.active.get_mut(&frame.node) {
+            *active -= 1;
+        }
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+    }
+
+    // counts a call of every function body in f
+    fn count_calls(&mut self, f: &Function) {
+        match f {
+            Function::Tree(pt) => *self.body_calls.entry(pt.id()).or_insert(0) += 1,
+            Function::Code(..) => (),
+            Function::Composed(first, second) => {
+                self.count_calls(first);
+                self.count_calls(second);
+            }
+        }
+    }
+}
+
+impl EvalObserver for Profiler {
+    fn on_enter_node(&mut self, node: &ParseTree, _input: &Value) {
+        let node = node.id();
+        let parent = self.stack.last().map(|f| f.path);
+        let next = self.paths.len();
+        let path = *self.path_ids.entry((parent, node)).or_insert(next);
+        if path == next {
+            self.paths.push((parent, node));
+            self.path_time.push(Duration::ZERO);
+        }
+        let active = self.active.entry(node).or_insert(0);
+        *active += 1;
+        let outermost = *active == 1;
+        self.stack.push(Frame { node, start: Instant::now(), children: Duration::ZERO, path, outermost });
+    }
+
+    fn on_exit_node(&mut self, _node: &ParseTree, _value: &Value) {
+        self.exit();
+    }
+
+    fn on_error(&mut self, _node: &ParseTree, _error: &RuntimeError) {
+        self.exit();
+    }
+
+    // counts a lazy list made by the node being evaluated
+    fn on_thunk_created(&mut self, _node: &ParseTree) {
+        if let Some(node) = self.stack.last().map(|f| f.node) {
+            self.nodes.entry(node).or_default().thunks += 1;
+        }
+    }
+
+    fn on_element_forcing(&mut self, _kind: &'static str, _index: i64, f: &Function) {
+        self.count_calls(f);
+    }
+}
+
+// every node in the expression by its id, function bodies included
+pub(crate) fn index(expression: &ParseTree) -> HashMap<NodeId, &ParseTree> {
+    fn walk<'a>(expression: &'a ParseTree, nodes: &mut HashMap<NodeId, &'a ParseTree>) {
+        match expression {
+            ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => (),
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} | ParseTree::Strict{arg, ..} |
+            ParseTree::Debug{arg, ..} => walk(arg, nodes),
+            // shared nodes are never entered themselves
+            ParseTree::Shared{arg, ..} => return walk(arg, nodes),
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => {
+                walk(arg1, nodes);
+                walk(arg2, nodes);
+            }
+        }
+        nodes.insert(expression.id(), expression);
+    }
+    let mut nodes = HashMap::new();
+    walk(expression, &mut nodes);
+    nodes
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// how long a piece of code is shown in the report before being cut off
+const CODE_WIDTH: usize = 40;
+
+impl Profile {
+    pub fn total(&self) -> Duration {
+        self.nodes.values().map(|n| n.exclusive).sum()
+    }
+
+    // Every node that did something, the slowest first.
+    pub fn report(&self, expression: &ParseTree) -> String {
+        let index = index(expression);
+        let mut nodes: Vec<_> = self.nodes.iter().collect();
+        nodes.sort_by(|(id1, n1), (id2, n2)| n2.exclusive.cmp(&n1.exclusive).then(id1.cmp(id2)));
+
+        let mut report = format!(
+            "{:>11} {:>11} {:>9} {:>7} {:>9}  node\n",
+            "exclusive", "inclusive", "evals", "thunks", "forced"
+        );
+        for ((line, col), n) in nodes {
+            let code = index.get(&(*line, *col)).map_or(String::new(), |node| {
+                let code = node.to_string();
+                match code.char_indices().nth(CODE_WIDTH) {
+                    Some((end, _)) => format!("{} {}...", node.form(), &code[..end]),
+                    None => format!("{} {}", node.form(), code),
+                }
+            });
+            report.push_str(&format!(
+                "{:>9.3}ms {:>9.3}ms {:>9} {:>7} {:>9}  {}:{} {}\n",
+                millis(n.exclusive), millis(n.inclusive), n.evaluations, n.thunks, n.forced, line, col, code
+            ));
+        }
+        report
+    }
+
+    // The source with the share of the exclusive time and the evaluations of the nodes that
+    // start on each line in front of it.
+    pub fn annotate(&self, source: &str) -> String {
+        let mut lines: HashMap<u32, (Duration, u64)> = HashMap::new();
+        for ((line, _), n) in &self.nodes {
+            let l = lines.entry(*line).or_default();
+            l.0 += n.exclusive;
+            l.1 += n.evaluations;
+        }
+        let total = self.total().as_secs_f64();
+        let mut annotated = String::new();
+        for (i, text) in source.lines().enumerate() {
+            match lines.get(&(i as u32 + 1)) {
+                Some((time, evals)) => {
+                    let share = if total > 0.0 { time.as_secs_f64() / total * 100.0 } else { 0.0 };
+                    annotated.push_str(&format!("{:>6.1}% {:>9} | {}\n", share, evals, text));
+                }
+                None => annotated.push_str(&format!("{:>7} {:>9} | {}\n", "", "", text)),
+            }
+        }
+        annotated
+    }
+
+    // The exclusive time of every stack of nodes in nanoseconds, one stack per line with
+    // its nodes separated by semicolons, which is the input flame graph tools expect.
+    pub fn collapsed_stacks(&self, expression: &ParseTree) -> String {
+        let index = index(expression);
+        let label = |id: NodeId| match index.get(&id) {
+            Some(node) => format!("{}@{}:{}", node.form(), id.0, id.1),
+            None => format!("{}:{}", id.0, id.1),
+        };
+        let mut stacks = String::new();
+        for (path, time) in self.path_time.iter().enumerate() {
+            if time.is_zero() {
+                continue;
+            }
+            let mut frames = Vec::new();
+            let mut next = Some(path);
+            while let Some(p) = next {
+                let (parent, node) = self.paths[p];
+                frames.push(label(node));
+                next = parent;
+            }
+            frames.reverse();
+            stacks.push_str(&format!("{} {}\n", frames.join(";"), time.as_nanos()));
+        }
+        stacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::evaluate::evaluate;
+    use crate::observer::{self, Observer};
+    use crate::parsetree::parse;
+
+    fn profiled(expr: &str) -> (ParseTree, Profile) {
+        let pt = parse(expr).expect("parse error");
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        let result = observer::observe(&[profiler.clone() as Observer], || {
+            evaluate(&pt, &Value::Number(0)).and_then(|v| v.force_resolve())
+        });
+        let profile = profiler.borrow_mut().finish(&pt);
+        assert!(result.is_ok());
+        (pt, profile)
+    }
+
+    #[test]
+    fn node_counts() {
+        let (_, profile) = profiled("1[](2[])(3[])[()(1))");
+        // the map, and its body once for each element
+        assert_eq!(profile.nodes[&(1, 14)].evaluations, 1);
+        assert_eq!(profile.nodes[&(1, 14)].thunks, 1);
+        assert_eq!(profile.nodes[&(1, 14)].forced, 3);
+        assert_eq!(profile.nodes[&(1, 17)].evaluations, 3);
+        // the element of X[] is computed when the whole list is forced
+        assert_eq!(profile.nodes[&(1, 2)].thunks, 1);
+        assert_eq!(profile.nodes[&(1, 2)].forced, 1);
+    }
+
+    #[test]
+    fn induction_counts() {
+        let (_, profile) = profiled("0(()(1)(())][3]");
+        let induction = profile.nodes[&(1, 2)];
+        assert_eq!((induction.evaluations, induction.thunks, induction.forced), (1, 1, 3));
+        // the elements are computed while indexing, so they count towards its time
+        assert!(profile.nodes[&(1, 13)].inclusive >= induction.inclusive);
+    }
+
+    #[test]
+    fn reports() {
+        let source = "1[](2[])\n[()(1))";
+        let (pt, profile) = profiled(source);
+        let report = profile.report(&pt);
+        assert!(report.lines().any(|l| l.ends_with("2:1 X[Y) 1[](2[])[()(1))")), "{}", report);
+
+        let annotated = profile.annotate(source);
+        assert_eq!(annotated.lines().count(), 2);
+        assert!(annotated.lines().all(|l| l.contains('%')));
+
+        let stacks = profile.collapsed_stacks(&pt);
+        assert!(stacks.lines().any(|l| l.starts_with("X[Y)@2:1;X(Y)@1:4;X[]@1:2 ")), "{}", stacks);
+        // the elements of the map are computed after it has been evaluated
+        assert!(stacks.lines().any(|l| l.starts_with("X(Y)@2:4 ")), "{}", stacks);
+    }
+}
@@ -0,0 +1,80 @@
+use std::cell::Cell;
+use std::fmt;
+
+// Counters for `--stats`. They only ever go up, so callers reset them before the run they
+// want to measure.
+thread_local! {
+    static NODES_EVALUATED: Cell<u64> = const { Cell::new(0) };
+    static SHARED_SUBEXPRESSIONS: Cell<u64> = const { Cell::new(0) };
+    static SHARED_REUSES: Cell<u64> = const { Cell::new(0) };
+    static FUSED_MAPS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    // nodes evaluated by the tree walker, or instructions executed by the vm
+    pub nodes_evaluated: u64,
+    // repeated subexpressions found by cse::share
+    pub shared_subexpressions: u64,
+    // times a shared subexpression was reused instead of being evaluated again
+    pub shared_reuses: u64,
+    // maps folded into the map or induction they were applied to, see maplist::map
+    pub fused_maps: u64,
+}
+
+pub fn get() -> Stats {
+    Stats {
+        nodes_evaluated: NODES_EVALUATED.with(Cell::get),
+        shared_subexpressions: SHARED_SUBEXPRESSIONS.with(Cell::get),
+        shared_reuses: SHARED_REUSES.with(Cell::get),
+        fused_maps: FUSED_MAPS.with(Cell::get),
+    }
+}
+
+pub fn reset() {
+    for counter in [&NODES_EVALUATED, &SHARED_SUBEXPRESSIONS, &SHARED_REUSES, &FUSED_MAPS] {
+        counter.with(|c| c.set(0));
+    }
+}
+
+// adds counts made on another thread, like the ones forcing map elements in parallel
+pub fn add(other: Stats) {
+    let counts = [
+        (&NODES_EVALUATED, other.nodes_evaluated),
+        (&SHARED_SUBEXPRESSIONS, other.shared_subexpressions),
+        (&SHARED_REUSES, other.shared_reuses),
+        (&FUSED_MAPS, other.fused_maps),
+    ];
+    for (counter, n) in counts {
+        counter.with(|c| c.set(c.get() + n));
+    }
+}
+
+fn increment(counter: &'static std::thread::LocalKey<Cell<u64>>) {
+    counter.with(|c| c.set(c.get() + 1));
+}
+
+pub(crate) fn count_node() {
+    increment(&NODES_EVALUATED);
+}
+
+pub(crate) fn count_shared_subexpression() {
+    increment(&SHARED_SUBEXPRESSIONS);
+}
+
+pub(crate) fn count_shared_reuse() {
+    increment(&SHARED_REUSES);
+}
+
+pub(crate) fn count_fused_map() {
+    increment(&FUSED_MAPS);
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "nodes evaluated: {}", self.nodes_evaluated)?;
+        writeln!(f, "shared subexpressions: {}", self.shared_subexpressions)?;
+        writeln!(f, "shared reuses: {}", self.shared_reuses)?;
+        write!(f, "fused maps: {}", self.fused_maps)
+    }
+}
@@ -0,0 +1,147 @@
+use super::parsetree::ParseTree;
+use super::sync::Rc;
+
+// Strictness analysis. X[] normally puts off evaluating X until the element is needed,
+// which costs a lazy list with a cache and a copy of the input. When evaluating X right
+// away can't be told apart from waiting, X[] is replaced by ParseTree::Strict. That is
+// when X:
+//  - can't raise an error, so no error happens that otherwise wouldn't, or happens sooner
+//  - has no debug operators, so nothing is printed in a different order
+//  - always finishes quickly, so it doesn't search for fixed points or index inductions
+// and also for X[][0], where X is needed right away anyway.
+pub fn mark(expression: &Rc<ParseTree>) -> Rc<ParseTree> {
+    let node = match &**expression {
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => return expression.clone(),
+
+        ParseTree::Encapsulate{arg, line, col} if safe(arg) => ParseTree::Strict{arg: mark(arg), line: *line, col: *col},
+
+        ParseTree::IndexSubtraction{arg1, arg2, line, col} => match (&**arg1, &**arg2) {
+            (ParseTree::Encapsulate{arg, line: eline, col: ecol}, ParseTree::Number{n: 0, ..}) => ParseTree::IndexSubtraction{
+                arg1: Rc::new(ParseTree::Strict{arg: mark(arg), line: *eline, col: *ecol}),
+                arg2: arg2.clone(),
+                line: *line,
+                col: *col,
+            },
+            _ => ParseTree::IndexSubtraction{arg1: mark(arg1), arg2: mark(arg2), line: *line, col: *col},
+        },
+
+        ParseTree::Length{arg, line, col} => ParseTree::Length{arg: mark(arg), line: *line, col: *col},
+        ParseTree::Encapsulate{arg, line, col} => ParseTree::Encapsulate{arg: mark(arg), line: *line, col: *col},
+        ParseTree::Strict{arg, line, col} => ParseTree::Strict{arg: mark(arg), line: *line, col: *col},
+        ParseTree::Debug{arg, line, col} => ParseTree::Debug{arg: mark(arg), line: *line, col: *col},
+        ParseTree::Shared{arg, slot} => ParseTree::Shared{arg: mark(arg), slot: *slot},
+        ParseTree::Addition{arg1, arg2, line, col} =>
+            ParseTree::Addition{arg1: mark(arg1), arg2: mark(arg2), line: *line, col: *col},
+        ParseTree::Induction{arg1, arg2, line, col} =>
+            ParseTree::Induction{arg1: mark(arg1), arg2: mark(arg2), line: *line, col: *col},
+        ParseTree::Map{arg1, arg2, line, col} =>
+            ParseTree::Map{arg1: mark(arg1), arg2: mark(arg2), line: *line, col: *col},
+    };
+    Rc::new(node)
+}
+
+// whether evaluating the expression certainly finishes quickly without an error or a print
+fn safe(expression: &ParseTree) -> bool {
+    match expression {
+        ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => true,
+        // the element itself is still lazy
+        ParseTree::Encapsulate{..} => true,
+        ParseTree::Strict{arg, ..} | ParseTree::Shared{arg, ..} => safe(arg),
+        // the length of a list literal is known without evaluating anything
+        ParseTree::Length{arg, ..} => constant(expression).is_some() || safe(arg) && matches!(
+            &**arg,
+            ParseTree::EmptyList{..} | ParseTree::Encapsulate{..} | ParseTree::Strict{..}
+        ),
+        // concatenation only looks at lengths, and not being able to get them isn't an error
+        ParseTree::Addition{arg1, arg2, ..} => constant(expression).is_some() ||
+            safe(arg1) && safe(arg2) && arg1.is_list() && arg2.is_list(),
+        // indexing can run into errors or take a while, so only subtraction is safe
+        ParseTree::IndexSubtraction{..} => constant(expression).is_some(),
+        // the body isn't evaluated until an element is needed
+        ParseTree::Induction{arg1, ..} => safe(arg1),
+        ParseTree::Map{arg1, ..} => safe(arg1) && arg1.is_list(),
+        ParseTree::Debug{..} => false,
+    }
+}
+
+// the value of arithmetic on number literals, unless it overflows (which panics)
+fn constant(expression: &ParseTree) -> Option<i64> {
+    match expression {
+        ParseTree::Number{n, ..} => Some(*n),
+        ParseTree::Length{arg, ..} => constant(arg)?.checked_abs(),
+        ParseTree::Addition{arg1, arg2, ..} => constant(arg1)?.checked_add(constant(arg2)?),
+        ParseTree::IndexSubtraction{arg1, arg2, ..} => constant(arg1)?.checked_sub(constant(arg2)?),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::parse;
+    use crate::value::Value;
+
+    // the code of every X[] that is evaluated right away
+    fn strict(expression: &ParseTree) -> Vec<String> {
+        let mut found = Vec::new();
+        match expression {
+            ParseTree::Number{..} | ParseTree::Input{..} | ParseTree::EmptyList{..} => (),
+            ParseTree::Strict{arg, ..} => {
+                found.push(expression.to_string());
+                found.extend(strict(arg));
+            }
+            ParseTree::Length{arg, ..} | ParseTree::Encapsulate{arg, ..} |
+            ParseTree::Debug{arg, ..} | ParseTree::Shared{arg, ..} => found.extend(strict(arg)),
+            ParseTree::Addition{arg1, arg2, ..} | ParseTree::IndexSubtraction{arg1, arg2, ..} |
+            ParseTree::Induction{arg1, arg2, ..} | ParseTree::Map{arg1, arg2, ..} => {
+                found.extend(strict(arg1));
+                found.extend(strict(arg2));
+            }
+        }
+        found
+    }
+
+    fn marked(expr: &str) -> Vec<String> {
+        strict(&mark(&Rc::new(parse(expr).expect("parse error"))))
+    }
+
+    #[test]
+    fn safe_elements() {
+        assert_eq!(marked("1[](2[])(3(4)[5][])"), vec!["1[]", "2[]", "3(4)[5][]"]);
+        assert_eq!(marked("()[](()()[])"), vec!["()[]"]);
+        assert_eq!(marked("1[](2[])[][](0(()(1)][])"), vec!["1[](2[])[][]", "1[](2[])[]", "1[]", "2[]", "0(()(1)][]"]);
+        assert_eq!(marked("0(()(1)][5][]"), Vec::<String>::new());
+        assert_eq!(marked("()(5)[]"), Vec::<String>::new());
+        assert_eq!(marked("5![]"), Vec::<String>::new());
+        // the element is needed right away
+        assert_eq!(marked("()[5][][0]"), vec!["()[5][]"]);
+        assert_eq!(marked("()[5][][1]"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn errors_stay_lazy() {
+        // including arithmetic that would overflow
+        for expr in ["[](1)[][]()", "9223372036854775807(1)[]()", "0[9223372036854775807][2][]()", "0[9223372036854775807][1]()[]()"] {
+            let pt = mark(&Rc::new(parse(expr).expect("parse error")));
+            let result = evaluate(&pt, &Value::Number(0)).expect("evaluation failure");
+            assert!(result == Value::Number(1), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn strict_elements_are_not_forced() {
+        // forcing X[] doesn't force X, however early X was evaluated
+        let pt = mark(&Rc::new(parse("0(()(1)][]").expect("parse error")));
+        assert_eq!(strict(&pt), vec!["0(()(1)][]"]);
+        let result = evaluate(&pt, &Value::Number(0)).expect("evaluation failure");
+        assert!(result.force_resolve().is_ok());
+        assert_eq!(format!("{}", result), "[[0, 1, 2, ...]]");
+    }
+
+    // every example should behave the same with and without strictness, on both backends
+    #[test]
+    fn strict_examples() {
+        crate::differential::behaves_the_same(&[], mark, true);
+    }
+}
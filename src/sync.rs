@@ -0,0 +1,51 @@
+// The pointer and interior mutability types that values are built from. With the parallel
+// feature they are swapped for thread safe ones under the same names, so that lists can be
+// forced on several threads at once (see MapList::force_resolve). Without it they are the
+// cheaper single threaded std types.
+
+#[cfg(not(feature = "parallel"))]
+pub use std::rc::Rc;
+#[cfg(not(feature = "parallel"))]
+pub use std::cell::RefCell;
+
+#[cfg(feature = "parallel")]
+pub use std::sync::Arc as Rc;
+#[cfg(feature = "parallel")]
+pub use self::parallel::RefCell;
+
+// what every list has to be to go inside an Rc
+#[cfg(not(feature = "parallel"))]
+pub trait Shareable {}
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> Shareable for T {}
+
+#[cfg(feature = "parallel")]
+pub trait Shareable: Send + Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Send + Sync + ?Sized> Shareable for T {}
+
+#[cfg(feature = "parallel")]
+mod parallel {
+    use std::sync::{Mutex, MutexGuard};
+
+    // A Mutex with the borrowing methods of a RefCell. Lists never borrow themselves while
+    // already borrowed, and only ever borrow lists that were made before them while
+    // borrowed, so locking can't deadlock.
+    pub struct RefCell<T>(Mutex<T>);
+
+    impl<T> RefCell<T> {
+        pub fn new(value: T) -> RefCell<T> {
+            RefCell(Mutex::new(value))
+        }
+
+        pub fn borrow(&self) -> MutexGuard<'_, T> {
+            self.borrow_mut()
+        }
+
+        // a panic on another thread doesn't leave the caches in a broken state, since they
+        // are only ever written whole values
+        pub fn borrow_mut(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+}
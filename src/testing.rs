@@ -0,0 +1,271 @@
+use super::evaluate::evaluate;
+use super::output;
+use super::parsetree::ParseTree;
+use super::sync::Rc;
+use super::value::{ExactList, StringList, Value};
+
+// how many differences between an expected and actual value are listed
+const MAX_DIFFERENCES: usize = 10;
+
+// A test written into a program as a comment, run by `labra-minus test`:
+//   # test: input=[7, 2] expect=[3, 1]
+//   # test: input="abc" expect-error=MismatchedTypes
+// Inputs and expected values are numbers, lists of them or strings in double quotes, and
+// expected errors are either the name of the error or its code, like LM0105. Without an
+// input the program is given 0, like on the command line.
+pub struct TestCase {
+    pub line: u32,
+    // the comment after test:
+    pub description: String,
+    pub input: Value,
+    pub expected: Expected,
+}
+
+pub enum Expected {
+    Value(Value),
+    Error(String),
+}
+
+// every test in the source, or the first comment that isn't a valid test
+pub fn cases(source: &str) -> Result<Vec<TestCase>, String> {
+    let mut cases = Vec::new();
+    for (i, l) in source.lines().enumerate() {
+        let linenum = i as u32 + 1;
+        let description = match l.find('#').and_then(|start| l[start + 1..].trim().strip_prefix("test:")) {
+            Some(description) => description.trim(),
+            None => continue,
+        };
+        let case = parse_case(linenum, description).map_err(|e| format!("line {}: {}", linenum, e))?;
+        cases.push(case);
+    }
+    Ok(cases)
+}
+
+fn parse_case(line: u32, description: &str) -> Result<TestCase, String> {
+    let chars: Vec<char> = description.chars().collect();
+    let mut pos = 0;
+    let (mut input, mut expected) = (None, None);
+    loop {
+        skip_whitespace(&chars, &mut pos);
+        if pos == chars.len() {
+            break;
+        }
+        let key: String = chars[pos..].iter().take_while(|c| **c != '=' && !c.is_whitespace()).collect();
+        pos += key.chars().count();
+        if chars.get(pos) != Some(&'=') {
+            return Err(format!("expected = after {}", key));
+        }
+        pos += 1;
+        let duplicate = match &key[..] {
+            "input" => input.replace(parse_value(&chars, &mut pos)?).is_some(),
+            "expect" => expected.replace(Expected::Value(parse_value(&chars, &mut pos)?)).is_some(),
+            "expect-error" => {
+                let name: String = chars[pos..].iter().take_while(|c| !c.is_whitespace()).collect();
+                if name.is_empty() {
+                    return Err(String::from("expect-error needs the name or code of an error"));
+                }
+                pos += name.chars().count();
+                expected.replace(Expected::Error(name)).is_some()
+            }
+            _ => return Err(format!("unknown key {}, expected input, expect or expect-error", key)),
+        };
+        if duplicate {
+            return Err(format!("{} is given twice", key));
+        }
+    }
+    Ok(TestCase {
+        line,
+        description: String::from(description),
+        input: input.unwrap_or(Value::Number(0)),
+        expected: expected.ok_or_else(|| String::from("a test needs an expect or expect-error"))?,
+    })
+}
+
+fn skip_whitespace(chars: &[char], pos: &mut usize) {
+    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+        *pos += 1;
+    }
+}
+
+// a number, a list like [1, [2, 3]] or a string like "a\"b"
+fn parse_value(chars: &[char], pos: &mut usize) -> Result<Value, String> {
+    skip_whitespace(chars, pos);
+    match chars.get(*pos) {
+        Some('[') => {
+            *pos += 1;
+            let mut elements = Vec::new();
+            skip_whitespace(chars, pos);
+            if chars.get(*pos) == Some(&']') {
+                *pos += 1;
+                return Ok(Value::List(Rc::new(ExactList::new(elements))));
+            }
+            loop {
+                elements.push(parse_value(chars, pos)?);
+                skip_whitespace(chars, pos);
+                match chars.get(*pos) {
+                    Some(',') => *pos += 1,
+                    Some(']') => {
+                        *pos += 1;
+                        return Ok(Value::List(Rc::new(ExactList::new(elements))));
+                    }
+                    _ => return Err(String::from("expected , or ] in list")),
+                }
+            }
+        }
+        Some('"') => {
+            *pos += 1;
+            let mut s = Vec::new();
+            loop {
+                match chars.get(*pos) {
+                    Some('"') => break,
+                    Some('\\') if *pos + 1 < chars.len() => {
+                        s.push(chars[*pos + 1]);
+                        *pos += 1;
+                    }
+                    Some(c) => s.push(*c),
+                    None => return Err(String::from("unclosed string")),
+                }
+                *pos += 1;
+            }
+            *pos += 1;
+            Ok(Value::List(Rc::new(StringList::new(s))))
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let start = *pos;
+            *pos += 1;
+            while chars.get(*pos).is_some_and(char::is_ascii_digit) {
+                *pos += 1;
+            }
+            let number: String = chars[start..*pos].iter().collect();
+            number.parse().map(Value::Number).map_err(|_| format!("{} is not a number", number))
+        }
+        _ => Err(String::from("expected a number, a list or a string in double quotes")),
+    }
+}
+
+// Runs program on the input of a test. Debug output is kept instead of printed, and shown
+// after the differences if the test fails.
+pub fn run(program: &ParseTree, case: &TestCase) -> Result<(), Vec<String>> {
+    let (result, printed) = output::capture(|| evaluate(program, &case.input)
+        .and_then(|v| { v.force_resolve()?; Ok(v) }));
+    let mut failures = match (&case.expected, result) {
+        (Expected::Value(expected), Ok(actual)) => diff(expected, &actual),
+        (Expected::Value(expected), Err(e)) =>
+            vec![format!("expected {}, got Runtime error[{}]: {}", expected, e.code(), e)],
+        (Expected::Error(name), Ok(actual)) => vec![format!("expected error {}, got {}", name, actual)],
+        (Expected::Error(name), Err(e)) if *name == e.name() || *name == e.code() => Vec::new(),
+        (Expected::Error(name), Err(e)) =>
+            vec![format!("expected error {}, got Runtime error[{}]: {}", name, e.code(), e)],
+    };
+    if failures.is_empty() {
+        return Ok(());
+    }
+    if !printed.is_empty() {
+        failures.push(String::from("debug output:"));
+        failures.extend(printed.lines().map(|l| format!("  {}", l)));
+    }
+    Err(failures)
+}
+
+// Where actual differs from expected, going into lists element by element so that one wrong
+// number deep in a list is reported as just that number. Both have to be finite.
+pub fn diff(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    compare(expected, actual, &mut String::from("result"), &mut differences);
+    differences
+}
+
+fn compare(expected: &Value, actual: &Value, path: &mut String, differences: &mut Vec<String>) {
+    if differences.len() == MAX_DIFFERENCES {
+        return;
+    }
+    let (e, a) = match (expected, actual) {
+        (Value::Number(e), Value::Number(a)) => {
+            if e != a {
+                differences.push(format!("at {}: expected {}, got {}", path, e, a));
+            }
+            return;
+        }
+        (Value::List(e), Value::List(a)) => (e, a),
+        _ => {
+            differences.push(format!("at {}: expected {}, got {}", path, expected, actual));
+            return;
+        }
+    };
+    let (elen, alen) = match (e.length(), a.length()) {
+        (Ok(elen), Ok(alen)) => (elen, alen),
+        (Err(err), _) | (_, Err(err)) => {
+            differences.push(format!("at {}: {}", path, err));
+            return;
+        }
+    };
+    for i in 0..elen.max(alen) {
+        let at = path.len();
+        path.push_str(&format!("[{}]", i));
+        match (e.index(i), a.index(i)) {
+            (Ok(ev), Ok(av)) => compare(&ev, &av, path, differences),
+            (Ok(ev), Err(_)) => differences.push(format!("at {}: missing {}", path, ev)),
+            (Err(_), Ok(av)) => differences.push(format!("at {}: unexpected {}", path, av)),
+            (Err(err), Err(_)) => differences.push(format!("at {}: {}", path, err)),
+        }
+        path.truncate(at);
+        if differences.len() == MAX_DIFFERENCES {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsetree::parse;
+
+    fn value(s: &str) -> Value {
+        parse_value(&s.chars().collect::<Vec<char>>(), &mut 0).expect("invalid value")
+    }
+
+    fn string_value(s: &str) -> Value {
+        Value::List(Rc::new(StringList::new(s.chars().collect())))
+    }
+
+    #[test]
+    fn parsing_cases() {
+        let source = "# test: input=[7, [2]] expect=3\n()[0] # test: input=\"a \\\"b\" expect-error=LM0105\n# not a test";
+        let cases = cases(source).expect("invalid test");
+        assert_eq!(cases.len(), 2);
+        assert_eq!((cases[0].line, &cases[0].description[..]), (1, "input=[7, [2]] expect=3"));
+        assert_eq!(cases[0].input, value("[7, [2]]"));
+        assert!(matches!(&cases[0].expected, Expected::Value(Value::Number(3))));
+        assert_eq!(cases[1].input, string_value("a \"b"));
+        assert!(matches!(&cases[1].expected, Expected::Error(e) if e == "LM0105"));
+
+        assert_eq!(super::cases("\n# test: input=1").err(), Some(String::from("line 2: a test needs an expect or expect-error")));
+        assert_eq!(super::cases("# test: expect=[1, 2").err(), Some(String::from("line 1: expected , or ] in list")));
+        assert_eq!(super::cases("# test: output=1").err(), Some(String::from("line 1: unknown key output, expected input, expect or expect-error")));
+    }
+
+    #[test]
+    fn running() {
+        let pt = parse("()[0](()[1])!").expect("parse error");
+        let cases = cases("# test: input=[7, 2] expect=9\n# test: input=[7, 2] expect=8\n# test: input=[7, [2]] expect-error=MismatchedTypes").expect("invalid test");
+        assert_eq!(run(&pt, &cases[0]), Ok(()));
+        assert_eq!(run(&pt, &cases[1]), Err(vec![
+            String::from("at result: expected 8, got 9"),
+            String::from("debug output:"),
+            String::from("  Debug at 1:13 - 9"),
+        ]));
+        assert_eq!(run(&pt, &cases[2]), Ok(()));
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(diff(&value("[1, [2, 3], 4]"), &value("[1, [2, 3], 4]")), Vec::<String>::new());
+        assert_eq!(diff(&value("[1, [2, 3], 4]"), &value("[1, [2, 5], 4]")), vec!["at result[1][1]: expected 3, got 5"]);
+        assert_eq!(diff(&value("[1, [2, 3]]"), &value("[1, 2, 6]")), vec![
+            "at result[1]: expected [2, 3], got 2",
+            "at result[2]: unexpected 6",
+        ]);
+        assert_eq!(diff(&value("[[1, 2]]"), &value("[[1]]")), vec!["at result[0][1]: missing 2"]);
+        assert_eq!(diff(&value("\"ab\""), &value("[97, 98]")), Vec::<String>::new());
+    }
+}
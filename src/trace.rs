@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use super::errors::RuntimeError;
+use super::observer::EvalObserver;
+use super::output::quote;
+use super::parsetree::{self, NodeId, ParseTree, Span};
+use super::profile;
+use super::value::{Function, ListLike, Value};
+
+// A log of everything the tree walker does, for `--trace` and `replay`. Lazy evaluation makes
+// it hard to tell from the program why things happen in the order they do, so the trace
+// records when each node is evaluated and each element is computed, and what for.
+// Events are written as they happen, one JSON object per line.
+
+// how many elements of a list are written out before the rest is left as ...
+const PREVIEW_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // a node started being evaluated
+    Enter,
+    // and finished, with a value or an error
+    Exit,
+    // an element of a lazy list started being computed
+    Force,
+    Forced,
+    // the node where an error happened, before the nodes around it give it up as well
+    Error,
+    // a debug operator printed a value
+    Debug,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub kind: Kind,
+    // how many nodes and elements were being evaluated when the event happened
+    pub depth: usize,
+    // the node the event is about. For Force and Forced, the node that made the list.
+    pub node: Option<NodeId>,
+    pub span: Option<Span>,
+    pub form: Option<String>,
+    // the element being computed
+    pub index: Option<i64>,
+    // the node evaluated when the element was needed, None when it was needed for the output
+    pub by: Option<NodeId>,
+    // () for Enter, and what was produced or printed otherwise
+    pub value: Option<String>,
+    // the code of an error, along with its message for Error
+    pub code: Option<String>,
+    pub message: Option<String>,
+}
+
+// Writes the events of everything evaluated while it is observing, see observer::observe.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    // the node that makes the list each function body computes the elements of, by the body
+    lists: HashMap<NodeId, NodeId>,
+    spans: HashMap<NodeId, Span>,
+    forms: HashMap<NodeId, &'static str>,
+    // the nodes being evaluated, with None for the elements being computed
+    stack: Vec<Option<NodeId>>,
+    // whether the error being passed up has already been recorded where it happened
+    raised: bool,
+    // the first error writing the trace, which stops anything more from being written
+    failed: Option<io::Error>,
+}
+
+impl Tracer {
+    // A tracer for evaluations of program, writing to out. The source is used to find where
+    // each node ends.
+    pub fn new(program: &ParseTree, source: &str, out: Box<dyn Write>) -> Tracer {
+        let nodes = profile::index(program);
+        let mut lists = HashMap::new();
+        for (id, node) in &nodes {
+            match node {
+                ParseTree::Encapsulate{arg, ..} => lists.insert(arg.id(), *id),
+                ParseTree::Induction{arg2, ..} | ParseTree::Map{arg2, ..} => lists.insert(arg2.id(), *id),
+                _ => None,
+            };
+        }
+        Tracer {
+            out,
+            lists,
+            spans: parsetree::spans(program, source),
+            forms: nodes.iter().map(|(id, node)| (*id, node.form())).collect(),
+            stack: Vec::new(),
+            raised: false,
+            failed: None,
+        }
+    }
+
+    // returns whether the whole trace could be written
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.failed.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    fn exit(&mut self, node: &ParseTree, result: Result<&Value, &RuntimeError>) {
+        let id = node.id();
+        if let Err(e) = result {
+            if !self.raised {
+                let mut event = self.event(Kind::Error, Some(id));
+                event.code = Some(e.code().to_owned());
+                event.message = Some(e.to_string());
+                self.write(&event);
+                self.raised = true;
+            }
+        }
+        self.stack.pop();
+        let mut event = self.event(Kind::Exit, Some(id));
+        self.result(&mut event, result);
+        self.write(&event);
+    }
+
+    // the list f computes the elements of
+    fn list(&self, f: &Function) -> Option<NodeId> {
+        body(f).and_then(|body| self.lists.get(&body).copied())
+    }
+}
+
+impl EvalObserver for Tracer {
+    fn on_enter_node(&mut self, node: &ParseTree, input: &Value) {
+        let id = node.id();
+        let mut event = self.event(Kind::Enter, Some(id));
+        event.value = Some(render(input));
+        self.write(&event);
+        self.stack.push(Some(id));
+        self.raised = false;
+    }
+
+    fn on_exit_node(&mut self, node: &ParseTree, value: &Value) {
+        self.exit(node, Ok(value));
+    }
+
+    fn on_error(&mut self, node: &ParseTree, error: &RuntimeError) {
+        self.exit(node, Err(error));
+    }
+
+    fn on_element_forcing(&mut self, _kind: &'static str, index: i64, f: &Function) {
+        let mut event = self.event(Kind::Force, self.list(f));
+        event.index = Some(index);
+        event.by = self.stack.iter().rev().find_map(|node| *node);
+        self.write(&event);
+        self.stack.push(None);
+    }
+
+    fn on_element_forced(&mut self, _kind: &'static str, index: i64, f: &Function, result: &Result<Value, RuntimeError>) {
+        self.stack.pop();
+        let mut event = self.event(Kind::Forced, self.list(f));
+        event.index = Some(index);
+        self.result(&mut event, result.as_ref());
+        self.write(&event);
+    }
+
+    fn on_debug(&mut self, line: u32, col: u32, value: &Value) -> bool {
+        let mut event = self.event(Kind::Debug, Some((line, col)));
+        event.value = Some(render(value));
+        self.write(&event);
+        false
+    }
+}
+
+// the function body that runs last, which belongs to the outermost of a chain of fused maps
+fn body(f: &Function) -> Option<NodeId> {
+    match f {
+        Function::Tree(pt) => Some(pt.id()),
+        Function::Code(..) => None,
+        Function::Composed(_, second) => body(second),
+    }
+}
+
+impl Tracer {
+    fn event(&self, kind: Kind, node: Option<NodeId>) -> Event {
+        Event {
+            kind,
+            depth: self.stack.len(),
+            node,
+            span: node.and_then(|id| self.spans.get(&id).copied()),
+            form: node.and_then(|id| self.forms.get(&id)).map(|form| form.to_string()),
+            index: None,
+            by: None,
+            value: None,
+            code: None,
+            message: None,
+        }
+    }
+
+    fn result(&self, event: &mut Event, result: Result<&Value, &RuntimeError>) {
+        match result {
+            Ok(v) => event.value = Some(render(v)),
+            Err(e) => event.code = Some(e.code().to_owned()),
+        }
+    }
+
+    fn write(&mut self, event: &Event) {
+        if self.failed.is_none() {
+            if let Err(e) = writeln!(self.out, "{}", event) {
+                self.failed = Some(e);
+            }
+        }
+    }
+}
+
+// Writes out a value without computing anything, since that could change what the program
+// does. Numbers and lists that already have all of their elements are written like the
+// output, and other lists only by their length.
+pub fn render(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::List(l) => {
+            let mut elements = Vec::new();
+            if preview(&**l, &mut elements) {
+                if elements.len() > PREVIEW_LENGTH {
+                    elements.truncate(PREVIEW_LENGTH);
+                    elements.push(String::from("..."));
+                }
+                format!("[{}]", elements.join(", "))
+            } else {
+                match l.length() {
+                    Ok(len) => format!("<list of {}>", len),
+                    Err(_) => String::from("<infinite list>"),
+                }
+            }
+        }
+    }
+}
+
+// Adds the elements of the list to elements, stopping once there are more than can be shown.
+// Returns false if one of them would have to be computed.
+fn preview(list: &dyn ListLike, elements: &mut Vec<String>) -> bool {
+    if let Some(chars) = list.as_chars() {
+        let left = PREVIEW_LENGTH + 1 - elements.len().min(PREVIEW_LENGTH + 1);
+        elements.extend(chars.iter().take(left).map(|c| u32::from(*c).to_string()));
+        true
+    } else if let Some(values) = list.as_values() {
+        let left = PREVIEW_LENGTH + 1 - elements.len().min(PREVIEW_LENGTH + 1);
+        elements.extend(values.iter().take(left).map(render));
+        true
+    } else if let Some(concat) = list.as_concat() {
+        let (first, second) = concat.sides();
+        preview(&**first, elements) && (elements.len() > PREVIEW_LENGTH || preview(&**second, elements))
+    } else {
+        false
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::Enter => "enter",
+            Kind::Exit => "exit",
+            Kind::Force => "force",
+            Kind::Forced => "forced",
+            Kind::Error => "error",
+            Kind::Debug => "debug",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = ParseTraceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enter" => Ok(Kind::Enter),
+            "exit" => Ok(Kind::Exit),
+            "force" => Ok(Kind::Force),
+            "forced" => Ok(Kind::Forced),
+            "error" => Ok(Kind::Error),
+            "debug" => Ok(Kind::Debug),
+            _ => Err(ParseTraceError(format!("unknown event \"{}\"", s))),
+        }
+    }
+}
+
+// One line of JSON, with the fields that aren't set left out. by is written as null for
+// elements needed by the output.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{\"event\":\"{}\",\"depth\":{}", self.kind, self.depth)?;
+        if let Some((line, col)) = self.node {
+            write!(f, ",\"node\":[{},{}]", line, col)?;
+        }
+        if let Some((l1, c1, l2, c2)) = self.span {
+            write!(f, ",\"span\":[{},{},{},{}]", l1, c1, l2, c2)?;
+        }
+        if let Some(form) = &self.form {
+            write!(f, ",\"form\":{}", quote(form))?;
+        }
+        if let Some(index) = self.index {
+            write!(f, ",\"index\":{}", index)?;
+        }
+        match self.by {
+            Some((line, col)) => write!(f, ",\"by\":[{},{}]", line, col)?,
+            None if self.kind == Kind::Force => write!(f, ",\"by\":null")?,
+            None => (),
+        }
+        for (key, field) in [("value", &self.value), ("code", &self.code), ("message", &self.message)] {
+            if let Some(s) = field {
+                write!(f, ",\"{}\":{}", key, quote(s))?;
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseTraceError(pub String);
+
+enum Json {
+    Number(i64),
+    Str(String),
+    Array(Vec<i64>),
+    Null,
+}
+
+// reads the flat objects Event is written as, which only hold numbers, strings, null and
+// arrays of numbers
+fn parse_object(s: &str) -> Result<HashMap<String, Json>, String> {
+    let mut chars = s.trim().chars().peekable();
+    let mut fields = HashMap::new();
+    if chars.next() != Some('{') {
+        return Err(String::from("expected {"));
+    }
+    fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+        if chars.next() != Some('"') {
+            return Err(String::from("expected a string"));
+        }
+        let mut s = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match chars.next() {
+                    Some('n') => s.push('\n'),
+                    Some('u') => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        let c = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{}", code))?;
+                        s.push(c);
+                    }
+                    Some(c) => s.push(c),
+                    None => return Err(String::from("unfinished string")),
+                },
+                Some(c) => s.push(c),
+                None => return Err(String::from("unfinished string")),
+            }
+        }
+    }
+    fn number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<i64, String> {
+        let mut s = String::new();
+        while let Some(c) = chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+            s.push(c);
+        }
+        s.parse().map_err(|_| format!("invalid number \"{}\"", s))
+    }
+    while chars.peek() != Some(&'}') {
+        let key = string(&mut chars)?;
+        if chars.next() != Some(':') {
+            return Err(format!("expected : after \"{}\"", key));
+        }
+        let value = match chars.peek() {
+            Some('"') => Json::Str(string(&mut chars)?),
+            Some('n') => {
+                let word: String = chars.by_ref().take(4).collect();
+                if word != "null" {
+                    return Err(format!("unexpected \"{}\"", word));
+                }
+                Json::Null
+            }
+            Some('[') => {
+                chars.next();
+                let mut numbers = Vec::new();
+                while chars.peek() != Some(&']') {
+                    numbers.push(number(&mut chars)?);
+                    chars.next_if_eq(&',');
+                }
+                chars.next();
+                Json::Array(numbers)
+            }
+            _ => Json::Number(number(&mut chars)?),
+        };
+        fields.insert(key, value);
+        chars.next_if_eq(&',');
+    }
+    Ok(fields)
+}
+
+impl FromStr for Event {
+    type Err = ParseTraceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = parse_object(s).map_err(ParseTraceError)?;
+        let mut string = |key: &str| match fields.remove(key) {
+            Some(Json::Str(s)) => Ok(Some(s)),
+            Some(Json::Null) | None => Ok(None),
+            Some(_) => Err(ParseTraceError(format!("{} should be a string", key))),
+        };
+        let kind = string("event")?.ok_or_else(|| ParseTraceError(String::from("missing event")))?.parse()?;
+        let (form, value, code, message) = (string("form")?, string("value")?, string("code")?, string("message")?);
+        let mut position = |key: &str| match fields.remove(key) {
+            Some(Json::Array(a)) if a.len() == 2 => Ok(Some((a[0] as u32, a[1] as u32))),
+            Some(Json::Null) | None => Ok(None),
+            Some(_) => Err(ParseTraceError(format!("{} should be a line and column", key))),
+        };
+        let (node, by) = (position("node")?, position("by")?);
+        let span = match fields.remove("span") {
+            Some(Json::Array(a)) if a.len() == 4 => Some((a[0] as u32, a[1] as u32, a[2] as u32, a[3] as u32)),
+            None => None,
+            Some(_) => return Err(ParseTraceError(String::from("span should be two lines and columns"))),
+        };
+        let mut number = |key: &str| match fields.remove(key) {
+            Some(Json::Number(n)) => Ok(Some(n)),
+            None => Ok(None),
+            Some(_) => Err(ParseTraceError(format!("{} should be a number", key))),
+        };
+        let depth = number("depth")?.ok_or_else(|| ParseTraceError(String::from("missing depth")))?;
+        Ok(Event {
+            kind,
+            depth: depth as usize,
+            node,
+            span,
+            form,
+            index: number("index")?,
+            by,
+            value,
+            code,
+            message,
+        })
+    }
+}
+
+fn position(id: Option<NodeId>) -> String {
+    match id {
+        Some((line, col)) => format!("{}:{}", line, col),
+        None => String::from("?"),
+    }
+}
+
+impl Event {
+    // what happened, in words
+    pub fn describe(&self) -> String {
+        let form = self.form.as_deref().unwrap_or("node");
+        let node = match self.span {
+            Some((l1, c1, l2, c2)) => format!("{} at {} ({}:{}-{}:{})", form, position(self.node), l1, c1, l2, c2),
+            None => format!("{} at {}", form, position(self.node)),
+        };
+        let result = match (&self.value, &self.code) {
+            (Some(value), _) => value.clone(),
+            (None, Some(code)) => format!("error {}", code),
+            (None, None) => String::from("?"),
+        };
+        let index = self.index.unwrap_or(0);
+        match self.kind {
+            Kind::Enter => format!("evaluating {} with () = {}", node, self.value.as_deref().unwrap_or("?")),
+            Kind::Exit => format!("{} gave {}", node, result),
+            Kind::Force => match self.by {
+                Some(by) => format!("computing element {} of {}, needed by {}", index, node, position(Some(by))),
+                None => format!("computing element {} of {}, needed by the output", index, node),
+            },
+            Kind::Forced => format!("element {} of {} is {}", index, node, result),
+            Kind::Error => format!(
+                "error {} raised by {}: {}",
+                self.code.as_deref().unwrap_or("?"), node, self.message.as_deref().unwrap_or("")
+            ),
+            Kind::Debug => format!("Debug at {} - {}", position(self.node), result),
+        }
+    }
+}
+
+pub struct Trace {
+    pub events: Vec<Event>,
+}
+
+impl FromStr for Trace {
+    type Err = ParseTraceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let events = s.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| line.parse().map_err(|ParseTraceError(e)| ParseTraceError(format!("line {}: {}", i + 1, e))))
+            .collect::<Result<_, _>>()?;
+        Ok(Trace { events })
+    }
+}
+
+const HELP: &str = "\
+commands:
+  n, next [N]          go forward one event, or N
+  b, back [N]          go back one event, or N
+  g, goto STEP         go to an event
+  p, print             show the current event
+  bt, chain            show the nodes being evaluated and the elements being computed
+  forced LINE:COL I    when element I of the list made at LINE:COL was computed, and what needed it
+  entered LINE:COL     when the node at LINE:COL was evaluated
+  debugs               every debug print
+  errors               where every error was raised
+  q, quit              stop replaying
+  h, help              show this
+an empty line repeats the last command
+";
+
+fn parse_position(s: &str) -> Option<NodeId> {
+    let (line, col) = s.split_once(':')?;
+    Some((line.trim().parse().ok()?, col.trim().parse().ok()?))
+}
+
+impl Trace {
+    pub fn show(&self, step: usize) -> String {
+        match self.events.get(step) {
+            Some(event) => format!("{:>6}: {}{}", step, "  ".repeat(event.depth), event.describe()),
+            None => format!("{:>6}: end of trace", step),
+        }
+    }
+
+    // the event where the node entered or element forced at step is done
+    fn end(&self, step: usize) -> Option<usize> {
+        let depth = self.events[step].depth;
+        (step + 1..self.events.len()).find(|i| self.events[*i].depth <= depth)
+    }
+
+    // the steps of the Enter and Force events that step happened inside of, innermost first
+    pub fn chain(&self, step: usize) -> Vec<usize> {
+        let mut stack = Vec::new();
+        for (i, event) in self.events.iter().enumerate().take(step) {
+            match event.kind {
+                Kind::Enter | Kind::Force => stack.push(i),
+                Kind::Exit | Kind::Forced => {
+                    stack.pop();
+                }
+                Kind::Error | Kind::Debug => (),
+            }
+        }
+        stack.reverse();
+        stack
+    }
+
+    // Goes through the trace with the commands read from commands, starting at the first event.
+    pub fn replay(&self, commands: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{} events, type help for a list of commands", self.events.len())?;
+        let mut step = 0;
+        writeln!(out, "{}", self.show(step))?;
+        let mut last_command = String::new();
+        loop {
+            write!(out, "(replay) ")?;
+            out.flush()?;
+            let mut line = String::new();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let mut line = line.trim().to_owned();
+            if line.is_empty() {
+                line = last_command.clone();
+            }
+            last_command = line.clone();
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let argument = words.next();
+            let count = argument.and_then(|n| n.parse::<usize>().ok()).unwrap_or(1);
+            match command {
+                "n" | "next" => {
+                    step = (step + count).min(self.events.len());
+                    writeln!(out, "{}", self.show(step))?;
+                }
+                "b" | "back" => {
+                    step = step.saturating_sub(count);
+                    writeln!(out, "{}", self.show(step))?;
+                }
+                "g" | "goto" => match argument.and_then(|n| n.parse::<usize>().ok()) {
+                    Some(n) => {
+                        step = n.min(self.events.len());
+                        writeln!(out, "{}", self.show(step))?;
+                    }
+                    None => writeln!(out, "goto needs the number of an event")?,
+                },
+                "p" | "print" => writeln!(out, "{}", self.show(step))?,
+                "bt" | "chain" => {
+                    for i in self.chain(step) {
+                        writeln!(out, "{}", self.show(i))?;
+                    }
+                }
+                "forced" => {
+                    let index = words.next().and_then(|i| i.parse::<i64>().ok());
+                    match (argument.and_then(parse_position), index) {
+                        (Some(list), Some(index)) => {
+                            let mut found = false;
+                            for (i, event) in self.events.iter().enumerate() {
+                                if event.kind == Kind::Force && event.node == Some(list) && event.index == Some(index) {
+                                    found = true;
+                                    writeln!(out, "{}", self.show(i))?;
+                                    for outer in self.chain(i) {
+                                        writeln!(out, "    inside {}", self.show(outer).trim_start())?;
+                                    }
+                                    if let Some(end) = self.end(i) {
+                                        writeln!(out, "{}", self.show(end))?;
+                                    }
+                                }
+                            }
+                            if !found {
+                                writeln!(out, "element {} of the list made at {}:{} was never computed", index, list.0, list.1)?;
+                            }
+                        }
+                        _ => writeln!(out, "forced needs the position of a list and an index, like 3:5 2")?,
+                    }
+                }
+                "entered" => match argument.and_then(parse_position) {
+                    Some(node) => {
+                        for (i, event) in self.events.iter().enumerate() {
+                            if event.kind == Kind::Enter && event.node == Some(node) {
+                                writeln!(out, "{}", self.show(i))?;
+                            }
+                        }
+                    }
+                    None => writeln!(out, "entered needs a position like 3:5")?,
+                },
+                "debugs" | "errors" => {
+                    let kind = if command == "debugs" { Kind::Debug } else { Kind::Error };
+                    for (i, event) in self.events.iter().enumerate() {
+                        if event.kind == kind {
+                            writeln!(out, "{}", self.show(i))?;
+                        }
+                    }
+                }
+                "q" | "quit" => return Ok(()),
+                "h" | "help" => write!(out, "{}", HELP)?,
+                other => writeln!(out, "unknown command \"{}\", try help", other)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use crate::evaluate::evaluate;
+    use crate::observer::{self, Observer};
+    use crate::parsetree::parse;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn traced(source: &str) -> String {
+        let pt = parse(source).expect("parse error");
+        let out = Shared::default();
+        let tracer = Rc::new(RefCell::new(Tracer::new(&pt, source, Box::new(out.clone()))));
+        let _ = observer::observe(&[tracer.clone() as Observer], || {
+            evaluate(&pt, &Value::Number(0)).and_then(|v| v.force_resolve())
+        });
+        tracer.borrow_mut().finish().expect("trace not written");
+        let trace = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        trace
+    }
+
+    #[test]
+    fn events() {
+        let trace = traced("1[](2[])\n[()(1))");
+        let lines: Vec<_> = trace.lines().collect();
+        assert_eq!(lines[0], r#"{"event":"enter","depth":0,"node":[2,1],"span":[1,1,2,7],"form":"X[Y)","value":"0"}"#);
+        assert!(lines.contains(&r#"{"event":"exit","depth":0,"node":[2,1],"span":[1,1,2,7],"form":"X[Y)","value":"<list of 2>"}"#), "{}", trace);
+        // the elements are computed for the output, after the map has been evaluated
+        assert!(lines.contains(&r#"{"event":"force","depth":0,"node":[2,1],"span":[1,1,2,7],"form":"X[Y)","index":1,"by":null}"#), "{}", trace);
+        assert!(lines.contains(&r#"{"event":"forced","depth":0,"node":[2,1],"span":[1,1,2,7],"form":"X[Y)","index":1,"value":"3"}"#), "{}", trace);
+        assert!(lines.contains(&r#"{"event":"enter","depth":1,"node":[2,4],"span":[2,2,2,6],"form":"X(Y)","value":"2"}"#), "{}", trace);
+    }
+
+    #[test]
+    fn errors_and_debugs() {
+        let trace = traced("1!(2[])");
+        let events: Vec<Event> = trace.lines().map(|l| l.parse().expect("invalid event")).collect();
+        assert_eq!(events.iter().filter(|e| e.kind == Kind::Error).count(), 1);
+        let error = events.iter().find(|e| e.kind == Kind::Error).unwrap();
+        assert_eq!((error.node, error.span, error.code.as_deref()), (Some((1, 3)), Some((1, 1, 1, 7)), Some("LM0105")));
+        let debug = events.iter().find(|e| e.kind == Kind::Debug).unwrap();
+        assert_eq!(debug.describe(), "Debug at 1:2 - 1");
+        // written out and read back the same
+        for (event, line) in events.iter().zip(trace.lines()) {
+            assert_eq!(event.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn rendering() {
+        let list: crate::value::ExactList = "[[1, 2], 3]".parse().unwrap();
+        assert_eq!(render(&Value::List(crate::sync::Rc::new(list))), "[[1, 2], 3]");
+        let long = crate::value::string::string_to_list("Hello, World!").ok().unwrap();
+        assert_eq!(render(&long), "[72, 101, 108, 108, 111, 44, 32, 87, 111, 114, ...]");
+        let event = Event {
+            kind: Kind::Error, depth: 2, node: None, span: None, form: None, index: None, by: None,
+            value: None, code: Some(String::from("LM0001")), message: Some(String::from("a \"quoted\"\nmessage\u{1}")),
+        };
+        assert_eq!(event.to_string().parse::<Event>(), Ok(event));
+    }
+
+    #[test]
+    fn replaying() {
+        let trace: Trace = traced("0(()(1)(())][2]").parse().expect("invalid trace");
+        let commands = "n 2\nb\n\nforced 1:2 2\nentered 1:1\ngoto 9\nbt\nq\nn\n";
+        let mut out = Vec::new();
+        trace.replay(&mut Cursor::new(commands), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("     2:     evaluating number at 1:1 (1:1-1:1) with () = 0"), "{}", out);
+        // an empty line goes back again
+        assert!(out.contains("     0: evaluating X[Y] at 1:13 (1:1-1:15) with () = 0\n(replay)     19:"), "{}", out);
+        assert!(out.contains("computing element 2 of X(Y] at 1:2 (1:1-1:12), needed by 1:13\n    inside 0: "), "{}", out);
+        assert!(out.contains("element 2 of X(Y] at 1:2 (1:1-1:12) is 3"), "{}", out);
+        assert!(out.contains("     9:       evaluating X(Y) at 1:5 (1:3-1:7) with () = 0\n(replay)      8: "), "{}", out);
+        // nothing after quit
+        assert!(out.ends_with("(replay) "), "{}", out);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::errors::{codes, TypeError};
+use super::parsetree::{NodeId, ParseTree};
+use super::value::Value;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Type {
+    Number,
+    List,
+    Unknown,
+}
+
+// whether an X[Y] node subtracts numbers or indexes a list
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IndexKind {
+    Index,
+    Subtraction,
+    Unknown,
+}
+
+pub struct TypeReport {
+    pub result: Type,
+    // errors every run that gets this far would hit
+    pub errors: Vec<TypeError>,
+    // errors in the bodies of maps, inductions and X[], which only happen if the body runs
+    pub warnings: Vec<TypeError>,
+    pub types: HashMap<NodeId, Type>,
+    pub index_kinds: HashMap<NodeId, IndexKind>,
+}
+
+impl TypeReport {
+    fn new() -> TypeReport {
+        TypeReport {
+            result: Type::Unknown,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            types: HashMap::new(),
+            index_kinds: HashMap::new(),
+        }
+    }
+
+    // lazy is whether the error is inside a body that may never run
+    fn error(&mut self, lazy: bool, error: TypeError) {
+        if lazy {
+            self.warnings.push(error);
+        } else {
+            self.errors.push(error);
+        }
+    }
+}
+
+impl Type {
+    pub fn of(v: &Value) -> Type {
+        match v {
+            Value::Number(_) => Type::Number,
+            Value::List(_) => Type::List,
+        }
+    }
+
+    fn join(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else {
+            Type::Unknown
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}",
+            match self {
+                Type::Number => "number",
+                Type::List => "list",
+                Type::Unknown => "unknown",
+            }
+        )
+    }
+}
+
+impl fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}",
+            match self {
+                IndexKind::Index => "index",
+                IndexKind::Subtraction => "subtraction",
+                IndexKind::Unknown => "index or subtraction",
+            }
+        )
+    }
+}
+
+// Infers the type of every node given the type of the program input.
+// Function bodies are checked even if they are never called, but since a lazy list may
+// never compute its elements, errors in them are only warnings.
+pub fn check(expression: &ParseTree, input: Type) -> TypeReport {
+    let mut report = TypeReport::new();
+    report.result = infer(expression, input, false, &mut report);
+    report
+}
+
+fn infer(expression: &ParseTree, input: Type, lazy: bool, report: &mut TypeReport) -> Type {
+    let t = match expression {
+        ParseTree::Number{..} => Type::Number,
+
+        ParseTree::Input{..} => input,
+
+        ParseTree::EmptyList{..} => Type::List,
+
+        ParseTree::Length{arg, ..} => {
+            infer(arg, input, lazy, report);
+            Type::Number
+        }
+
+        ParseTree::Encapsulate{arg, ..} => {
+            infer(arg, input, true, report);
+            Type::List
+        }
+
+        // evaluated right away, see strictness::mark
+        ParseTree::Strict{arg, ..} => {
+            infer(arg, input, lazy, report);
+            Type::List
+        }
+
+        ParseTree::Addition{arg1, arg2, line, col} => {
+            match (infer(arg1, input, lazy, report), infer(arg2, input, lazy, report)) {
+                (Type::Number, Type::List) | (Type::List, Type::Number) => {
+                    report.error(lazy, TypeError::MismatchedTypes(codes::ADD_NUMBER_AND_LIST, format!(
+                        "Cannot add number and list at {}:{}",
+                        line, col
+                    )));
+                    Type::Unknown
+                }
+                // otherwise both sides agree, or one of them is unknown
+                (Type::Unknown, t) | (t, _) => t,
+            }
+        }
+
+        ParseTree::IndexSubtraction{arg1, arg2, line, col} => {
+            let t1 = infer(arg1, input, lazy, report);
+            let t2 = infer(arg2, input, lazy, report);
+            if t2 == Type::List {
+                report.error(lazy, TypeError::MismatchedTypes(codes::SUBTRACT_OR_INDEX_WITH_LIST, format!(
+                    "Cannot subtract or index with list at {}:{}",
+                    line, col
+                )));
+            }
+            let kind = match t1 {
+                Type::Number => IndexKind::Subtraction,
+                Type::List => IndexKind::Index,
+                Type::Unknown => IndexKind::Unknown,
+            };
+            report.index_kinds.insert(expression.id(), kind);
+            match kind {
+                IndexKind::Subtraction => Type::Number,
+                _ => Type::Unknown,
+            }
+        }
+
+        ParseTree::Induction{arg1, arg2, ..} => {
+            let init = infer(arg1, input, lazy, report);
+            // the body sees the initial value and then its own outputs, so only keep the
+            // initial type if the body preserves it
+            let mut scratch = TypeReport::new();
+            let body_input = init.join(infer(arg2, init, true, &mut scratch));
+            infer(arg2, body_input, true, report);
+            Type::List
+        }
+
+        ParseTree::Map{arg1, arg2, line, col} => {
+            if infer(arg1, input, lazy, report) == Type::Number {
+                report.error(lazy, TypeError::MismatchedTypes(codes::MAP_NUMBER, format!(
+                    "Attempt to map number at {}:{}",
+                    line, col
+                )));
+            }
+            // nothing is known about the elements of a list
+            infer(arg2, Type::Unknown, true, report);
+            Type::List
+        }
+
+        ParseTree::Debug{arg, ..} | ParseTree::Shared{arg, ..} => infer(arg, input, lazy, report),
+    };
+    report.types.insert(expression.id(), t);
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsetree::parse;
+
+    #[test]
+    fn simple_types() {
+        let pt = parse("1(2)").expect("parse error");
+        assert_eq!(check(&pt, Type::Unknown).result, Type::Number);
+        let pt = parse("1[](2[])").expect("parse error");
+        assert_eq!(check(&pt, Type::Unknown).result, Type::List);
+        let pt = parse("()").expect("parse error");
+        assert_eq!(check(&pt, Type::List).result, Type::List);
+        assert_eq!(check(&pt, Type::Unknown).result, Type::Unknown);
+    }
+
+    #[test]
+    fn mismatched_addition() {
+        let pt = parse("1([])").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            format!("{}", report.errors[0]),
+            "Mismatched Types - Cannot add number and list at 1:2"
+        );
+    }
+
+    #[test]
+    fn input_dependent_errors() {
+        let pt = parse("()[0)").expect("parse error");
+        assert_eq!(check(&pt, Type::Number).errors.len(), 1);
+        assert_eq!(check(&pt, Type::List).errors.len(), 0);
+        assert_eq!(check(&pt, Type::Unknown).errors.len(), 0);
+    }
+
+    #[test]
+    fn error_in_map_body() {
+        let pt = parse("0[][()([]))").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!((report.errors.len(), report.warnings.len()), (0, 0));
+        // the body may never be called
+        let pt = parse("0[][1([]))").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!((report.errors.len(), report.warnings.len()), (0, 1));
+    }
+
+    #[test]
+    fn errors_in_bodies_that_never_run() {
+        // both run and print 1
+        for expr in ["1([])[]()", "[][1([]))()"] {
+            let pt = parse(expr).expect("parse error");
+            let report = check(&pt, Type::Number);
+            assert_eq!(report.errors.len(), 0, "{}", expr);
+            assert_eq!(report.warnings.len(), 1, "{}", expr);
+            assert_eq!(report.result, Type::Number, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn index_disambiguation() {
+        let pt = parse("5[1][](2[])[0]").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!(report.index_kinds[&(1, 2)], IndexKind::Subtraction);
+        assert_eq!(report.index_kinds[&(1, 12)], IndexKind::Index);
+        assert_eq!(report.result, Type::Unknown);
+    }
+
+    #[test]
+    fn induction_body_input() {
+        // the body keeps the input a number, so ()[1] is a subtraction
+        let pt = parse("0(()[1]]").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!(report.index_kinds[&(1, 5)], IndexKind::Subtraction);
+        // the body turns numbers into lists, so the input could be either
+        let pt = parse("0(()[1][]]").expect("parse error");
+        let report = check(&pt, Type::Number);
+        assert_eq!(report.index_kinds[&(1, 5)], IndexKind::Unknown);
+    }
+}
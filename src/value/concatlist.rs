@@ -1,30 +1,124 @@
-use std::rc::Rc;
-
-use super::{ListLike, Value};
+use super::{ListLike, Part, Parts, StringList, Value};
 
 use crate::errors::RuntimeError;
+use crate::sync::Rc;
+
+// Concatenations of finite lists form a balanced tree (like a rope), so that building a
+// list one element at a time doesn't create a chain that every index has to walk down.
+// The elements themselves are never looked at, so they stay as lazy as they were.
+// Strings up to this long are copied into one when they are concatenated. Longer ones are
+// joined like any other list, so that building a long string doesn't copy it over and over.
+const MAX_MERGED_STRING: i64 = 4096;
 
 pub struct ConcatList {
     first: Rc<dyn ListLike>,
     second: Rc<dyn ListLike>,
     firstlen: Option<i64>,
+    // only known when both sides are finite, which is also when the list is kept balanced
+    length: Option<i64>,
+    // 0 for lists that aren't balanced concatenations
+    height: usize,
 }
 
 impl ConcatList {
+    // The result is only a ConcatList with l1 and l2 as its sides if they are already
+    // balanced against each other, so this returns the more general type.
+    pub fn concat(l1: Rc<dyn ListLike>, l2: Rc<dyn ListLike>) -> Rc<dyn ListLike> {
+        match (l1.length(), l2.length()) {
+            (Ok(len1), Ok(len2)) if len1 + len2 <= MAX_MERGED_STRING => match (l1.as_string(), l2.as_string()) {
+                (Some(s1), Some(s2)) => Rc::new(StringList::join(s1, s2)),
+                _ => join(l1, l2),
+            },
+            (Ok(_), Ok(_)) => join(l1, l2),
+            _ => Rc::new(ConcatList::new(l1, l2)),
+        }
+    }
+
     pub fn new(l1: Rc<dyn ListLike>, l2: Rc<dyn ListLike>) -> ConcatList {
-        let fl = l1.length()
-            .and_then(|n| i64::try_from(n).map_err(|_| RuntimeError::OutOfBounds(String::from("not sure what happened here"))))
-            .ok();
+        let fl = l1.length().ok();
+        let length = match (fl, l2.length()) {
+            (Some(len1), Ok(len2)) => Some(len1 + len2),
+            _ => None,
+        };
+        let height = match length {
+            Some(_) => 1 + height(&l1).max(height(&l2)),
+            None => 0,
+        };
         ConcatList {
             first: l1,
             second: l2,
             firstlen: fl,
+            length,
+            height,
         }
     }
+
+    pub fn sides(&self) -> (&Rc<dyn ListLike>, &Rc<dyn ListLike>) {
+        (&self.first, &self.second)
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+fn height(l: &Rc<dyn ListLike>) -> usize {
+    l.as_concat().map_or(0, |c| c.height)
+}
+
+// the two sides of a list with a height above 0
+fn sides(l: &Rc<dyn ListLike>) -> (Rc<dyn ListLike>, Rc<dyn ListLike>) {
+    let c = l.as_concat().expect("only concatenations have a height");
+    (c.first.clone(), c.second.clone())
+}
+
+fn node(l1: Rc<dyn ListLike>, l2: Rc<dyn ListLike>) -> Rc<dyn ListLike> {
+    Rc::new(ConcatList::new(l1, l2))
+}
+
+// AVL tree join: walks down the side of the taller tree until the heights match, then
+// rebalances on the way back up. Takes time proportional to the difference in heights.
+fn join(l1: Rc<dyn ListLike>, l2: Rc<dyn ListLike>) -> Rc<dyn ListLike> {
+    let (h1, h2) = (height(&l1), height(&l2));
+    if h1 > h2 + 1 {
+        let (a, b) = sides(&l1);
+        balance(a, join(b, l2))
+    } else if h2 > h1 + 1 {
+        let (a, b) = sides(&l2);
+        balance(join(l1, a), b)
+    } else {
+        node(l1, l2)
+    }
+}
+
+// concatenates two lists whose heights differ by at most 2, rotating if they differ by 2
+fn balance(l1: Rc<dyn ListLike>, l2: Rc<dyn ListLike>) -> Rc<dyn ListLike> {
+    let (h1, h2) = (height(&l1), height(&l2));
+    if h2 > h1 + 1 {
+        let (a, b) = sides(&l2);
+        if height(&a) > height(&b) {
+            let (a1, a2) = sides(&a);
+            node(node(l1, a1), node(a2, b))
+        } else {
+            node(node(l1, a), b)
+        }
+    } else if h1 > h2 + 1 {
+        let (a, b) = sides(&l1);
+        if height(&b) > height(&a) {
+            let (b1, b2) = sides(&b);
+            node(node(a, b1), node(b2, l2))
+        } else {
+            node(a, node(b, l2))
+        }
+    } else {
+        node(l1, l2)
+    }
 }
 
 impl ListLike for ConcatList {
     fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        // negative indexes go to the first side and indexes past the end to the second, so
+        // they reach the leftmost and rightmost of the lists joined, however it is balanced
         match self.firstlen {
             None => self.first.index(i),
             Some(len) => {
@@ -38,11 +132,29 @@ impl ListLike for ConcatList {
     }
 
     fn length(&self) -> Result<i64, RuntimeError> {
-        return Ok(self.first.length()? + self.second.length()?);
+        match self.length {
+            Some(len) => Ok(len),
+            None => Ok(self.first.length()? + self.second.length()?),
+        }
     }
 
     fn force_resolve(&self) -> Result<(), RuntimeError> {
         self.first.force_resolve()?;
         self.second.force_resolve()
     }
+
+    fn as_concat(&self) -> Option<&ConcatList> {
+        Some(self)
+    }
+
+    fn parts(&self) -> Parts {
+        Parts {
+            kind: "ConcatList",
+            label: self.length.map_or(String::from("infinite"), |len| format!("length {}", len)),
+            children: vec![
+                (String::from("first"), Part::Value(Value::List(self.first.clone()))),
+                (String::from("second"), Part::Value(Value::List(self.second.clone()))),
+            ],
+        }
+    }
 }
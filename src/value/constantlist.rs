@@ -0,0 +1,49 @@
+use super::{ListLike, Part, Parts, Value};
+
+use crate::errors::{codes, RuntimeError};
+
+// The inductions X(()], which repeats X forever, and X(k], which is X followed by k forever.
+// Every negative index finds the fixed point k.
+pub struct ConstantList {
+    first: Value,
+    rest: Value,
+}
+
+impl ConstantList {
+    pub fn new(value: Value) -> ConstantList {
+        ConstantList { first: value.clone(), rest: value }
+    }
+
+    pub fn after(first: Value, rest: Value) -> ConstantList {
+        ConstantList { first, rest }
+    }
+}
+
+impl ListLike for ConstantList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        if i == 0 {
+            Ok(self.first.clone())
+        } else {
+            Ok(self.rest.clone())
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::INFINITE_LENGTH, String::from("Cannot get length of infinite list")))
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::FORCING_INFINITE_LIST, "Attempted to force_resolve an infinite list. (Does your final output include one?)".to_owned()))
+    }
+
+    fn parts(&self) -> Parts {
+        Parts {
+            kind: "ConstantList",
+            label: String::new(),
+            children: vec![
+                (String::from("[0]"), Part::Value(self.first.clone())),
+                (String::from("[1..]"), Part::Value(self.rest.clone())),
+            ],
+        }
+    }
+}
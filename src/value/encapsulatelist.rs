@@ -1,32 +1,42 @@
-use std::cell::RefCell;
+use super::{Function, ListLike, Part, Parts, Value};
 
-use super::{ListLike, Value};
-
-use crate::evaluate::evaluate;
-use crate::errors::RuntimeError;
-use crate::parsetree::ParseTree;
+use crate::errors::{codes, RuntimeError};
+use crate::sync::RefCell;
 
 pub struct EncapsulateList {
-    subtree: ParseTree,
+    // None for elements that were evaluated right away
+    subtree: Option<Function>,
     env_input: Value,
     value: RefCell<Option<Result<Value, RuntimeError>>>
 }
 
 impl EncapsulateList{
-    pub fn new(subtree: ParseTree, env_input: Value) -> Self{
+    pub fn new(subtree: impl Into<Function>, env_input: Value) -> Self{
         EncapsulateList{
-            subtree,
+            subtree: Some(subtree.into()),
             env_input,
             value: RefCell::new(None)
         }
     }
 
+    // the list X[] for an X that is already known, which still doesn't force X when it is
+    // forced itself
+    pub fn evaluated(value: Value) -> Self{
+        EncapsulateList{
+            subtree: None,
+            env_input: Value::Number(0),
+            value: RefCell::new(Some(Ok(value)))
+        }
+    }
+
     pub fn get(&self) -> Result<Value, RuntimeError>{
         let mut value = self.value.borrow_mut();
         if let Some(v) = value.clone(){
             v
         }else{
-            let v = evaluate(&self.subtree, &self.env_input);
+            let f = self.subtree.as_ref()
+                .expect("elements without a function are evaluated when they are made");
+            let v = f.call_element("encapsulation", 0, &self.env_input);
             *value = Some(v.clone());
             v
         }
@@ -38,15 +48,33 @@ impl ListLike for EncapsulateList{
         if i == 0 || i == -1{
             self.get()
         }else{
-            Err(RuntimeError::OutOfBounds(format!(
-                "Attempted to access index {} of list of length 0", i
+            Err(RuntimeError::OutOfBounds(codes::ENCAPSULATE_OUT_OF_BOUNDS, format!(
+                "Attempted to access index {} of list of length 1", i
             )))
         }
     }
     fn length(&self) -> Result<i64, RuntimeError>{
-        return Ok(1);
+        Ok(1)
     }
     fn force_resolve(&self) -> Result<(), RuntimeError>{
         self.get().map(|_|())
     }
+
+    fn parts(&self) -> Parts {
+        let mut children = Vec::new();
+        let label = match (&self.subtree, &*self.value.borrow()) {
+            (_, Some(Err(e))) => format!("failed with {}", e.code()),
+            (Some(f), _) => format!("of {}", f),
+            (None, _) => String::from("evaluated right away"),
+        };
+        match &*self.value.borrow() {
+            Some(Ok(v)) => children.push((String::from("[0]"), Part::Value(v.clone()))),
+            Some(Err(_)) => {}
+            None => children.push((String::from("[0]"), Part::Thunk)),
+        }
+        if self.subtree.is_some() {
+            children.push((String::from("()"), Part::Value(self.env_input.clone())));
+        }
+        Parts { kind: "EncapsulateList", label, children }
+    }
 }
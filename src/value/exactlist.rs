@@ -1,10 +1,10 @@
 use std::str::FromStr;
-use std::rc::Rc;
 
-use super::{ListLike, Value};
+use super::{ListLike, Part, Parts, Value};
 use super::string;
 
-use crate::errors::RuntimeError;
+use crate::errors::{codes, RuntimeError};
+use crate::sync::Rc;
 
 pub struct ExactList {
     contents: Vec<Value>,
@@ -22,7 +22,7 @@ impl ListLike for ExactList {
         let len = self.length()?;
 
         if i >= len || i < -len{
-            return Err(RuntimeError::OutOfBounds(format!(
+            return Err(RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!(
                 "Attempted to access index {} of list of length {}",
                 i,
                 self.contents.len()
@@ -34,14 +34,14 @@ impl ListLike for ExactList {
         }
 
         let trueindex = usize::try_from(trueindex)
-            .map_err(|_| RuntimeError::OutOfBounds(format!("unknown error when indexing list (i = {})", i)))?;
+            .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!("unknown error when indexing list (i = {})", i)))?;
 
         Ok(self.contents[trueindex].clone())
     }
 
     fn length(&self) -> Result<i64, RuntimeError> {
-        return i64::try_from(self.contents.len())
-            .map_err(|_| RuntimeError::OutOfBounds(String::from("length could not be converted to i64")));
+        i64::try_from(self.contents.len())
+            .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, String::from("length could not be converted to i64")))
     }
 
     fn force_resolve(&self) -> Result<(), RuntimeError> {
@@ -50,6 +50,21 @@ impl ListLike for ExactList {
         }
         Ok(())
     }
+
+    fn as_values(&self) -> Option<&[Value]> {
+        Some(&self.contents)
+    }
+
+    fn parts(&self) -> Parts {
+        Parts {
+            kind: "ExactList",
+            label: format!("length {}", self.contents.len()),
+            children: self.contents.iter()
+                .enumerate()
+                .map(|(i, v)| (format!("[{}]", i), Part::Value(v.clone())))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -67,10 +82,10 @@ impl FromStr for ExactList{
         let mut sections = Vec::<&str>::new();
         let mut lastcomma = 0;
         let mut depth = 0;
-        for (i, c) in s.chars().enumerate(){
+        for (i, c) in s.char_indices(){
             if i == 0 {
                 continue;
-            } else if i == s.chars().count()-1 {
+            } else if i == s.len()-1 {
                 if depth != 0{
                     return Err(ParseExactListError);
                 }
@@ -103,7 +118,7 @@ impl FromStr for ExactList{
             }
         );
 
-        return Ok(ExactList{
+        Ok(ExactList{
             contents: values.collect()
         })
     }
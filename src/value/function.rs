@@ -0,0 +1,117 @@
+use std::fmt;
+
+use super::Value;
+
+use crate::evaluate;
+use crate::errors::RuntimeError;
+use crate::parsetree::ParseTree;
+use crate::observer;
+use crate::output;
+use crate::sync::Rc;
+use crate::vm::{self, Instruction};
+
+// The body of a map, induction or encapsulation, which lazy lists call to produce their elements.
+// Each backend has its own way of pointing at the code to run.
+#[derive(Clone)]
+pub enum Function {
+    Tree(Rc<ParseTree>),
+    // a compiled program and the index of the function in it
+    Code(Rc<vm::Program>, usize),
+    // the first function followed by the second, see ListLike::fuse_map
+    Composed(Rc<Function>, Rc<Function>),
+}
+
+// What a function does to its input, for functions simple enough to not need calling
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Step {
+    // ()
+    Identity,
+    // a number k, which ignores the input
+    Constant(i64),
+    // ()(k), k(()) or ()[k], which are only additions when the input is a number
+    Add(i64),
+}
+
+impl Function {
+    pub fn call(&self, input: &Value) -> Result<Value, RuntimeError> {
+        match self {
+            Function::Tree(pt) => evaluate::evaluate(pt, input),
+            Function::Code(program, function) => vm::run(program, *function, input),
+            Function::Composed(first, second) => second.call(&first.call(input)?),
+        }
+    }
+
+    // Calls the function to compute element index of a lazy list, telling the observers.
+    // kind is the sort of list, like "map".
+    pub fn call_element(&self, kind: &'static str, index: i64, input: &Value) -> Result<Value, RuntimeError> {
+        output::nested(|| observer::element(kind, index, self, || self.call(input)))
+    }
+
+    pub fn compose(first: Function, second: Function) -> Function {
+        Function::Composed(Rc::new(first), Rc::new(second))
+    }
+
+    // whether calling the function could print anything. Compiled functions are checked
+    // along with every other function in their program.
+    pub fn has_debug(&self) -> bool {
+        match self {
+            Function::Tree(pt) => pt.has_debug(),
+            Function::Code(program, _) => program.functions.iter()
+                .flatten()
+                .any(|instruction| matches!(instruction, Instruction::Debug{..})),
+            Function::Composed(first, second) => first.has_debug() || second.has_debug(),
+        }
+    }
+
+    pub fn step(&self) -> Option<Step> {
+        match self {
+            Function::Tree(pt) => match &**pt {
+                ParseTree::Input{..} => Some(Step::Identity),
+                ParseTree::Number{n, ..} => Some(Step::Constant(*n)),
+                ParseTree::Addition{arg1, arg2, ..} => match (&**arg1, &**arg2) {
+                    (ParseTree::Input{..}, ParseTree::Number{n, ..}) |
+                    (ParseTree::Number{n, ..}, ParseTree::Input{..}) => Some(Step::Add(*n)),
+                    _ => None,
+                },
+                ParseTree::IndexSubtraction{arg1, arg2, ..} => match (&**arg1, &**arg2) {
+                    (ParseTree::Input{..}, ParseTree::Number{n, ..}) => n.checked_neg().map(Step::Add),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Function::Code(program, function) => match &program.functions[*function][..] {
+                [Instruction::Input] => Some(Step::Identity),
+                [Instruction::Number(n)] => Some(Step::Constant(*n)),
+                [Instruction::Input, Instruction::Number(n), Instruction::Addition{..}] |
+                [Instruction::Number(n), Instruction::Input, Instruction::Addition{..}] => Some(Step::Add(*n)),
+                [Instruction::Input, Instruction::Number(n), Instruction::IndexSubtraction{..}] =>
+                    n.checked_neg().map(Step::Add),
+                _ => None,
+            },
+            Function::Composed(..) => None,
+        }
+    }
+}
+
+// The code of the function, with compiled functions given by their index in the program
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Function::Tree(pt) => write!(f, "{}", pt),
+            Function::Code(_, function) => write!(f, "function {}", function),
+            Function::Composed(first, second) => write!(f, "{} then {}", first, second),
+        }
+    }
+}
+
+impl From<Rc<ParseTree>> for Function {
+    fn from(pt: Rc<ParseTree>) -> Function {
+        Function::Tree(pt)
+    }
+}
+
+impl From<ParseTree> for Function {
+    fn from(pt: ParseTree) -> Function {
+        Function::Tree(Rc::new(pt))
+    }
+}
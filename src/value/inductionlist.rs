@@ -1,66 +1,212 @@
-use std::cell::RefCell;
+use std::cell::Cell;
 
-use super::{ListLike, Value};
+use super::{thunks, ConstantList, Function, ListLike, Part, Parts, ProgressionList, Step, Value};
 
-use crate::evaluate;
-use crate::errors::RuntimeError;
-use crate::parsetree::ParseTree;
+use crate::errors::{codes, RuntimeError};
+use crate::sync::{RefCell, Rc};
+
+thread_local! {
+    static CHECKPOINT_INTERVAL: Cell<usize> = const { Cell::new(1) };
+    static CLOSED_FORMS: Cell<bool> = const { Cell::new(true) };
+}
+
+// How many elements apart the values kept by new InductionLists are. 1 keeps every element.
+// With a larger interval, elements between two checkpoints are recomputed from the one
+// before them when they are indexed again, trading time for memory.
+pub fn set_checkpoint_interval(interval: usize) {
+    CHECKPOINT_INTERVAL.with(|c| c.set(interval.max(1)));
+}
+
+pub fn checkpoint_interval() -> usize {
+    CHECKPOINT_INTERVAL.with(Cell::get)
+}
+
+// Whether induction() uses closed forms. They never call the body, so coverage turns them
+// off to see it evaluated.
+pub fn set_closed_forms(enabled: bool) {
+    CLOSED_FORMS.with(|c| c.set(enabled));
+}
+
+pub fn closed_forms() -> bool {
+    CLOSED_FORMS.with(Cell::get)
+}
 
 pub struct InductionList {
-    function: ParseTree,
-    initial_value: Value,
+    function: Function,
+    // the elements at 0, interval, 2*interval, ...
     resolved: RefCell<Vec<Value>>,
+    interval: usize,
+    // the furthest element computed so far and its index, which is where indexing further
+    // along the list picks up from
+    latest: RefCell<(usize, Value)>,
+    fixed_point: RefCell<Option<Value>>,
+    // applied to each element on the way out, when a map has been fused into the list
+    map: Option<Function>,
+}
+
+// Builds the list init(f], using a closed form instead of an InductionList when f is
+// simple enough that its elements can be computed directly.
+pub fn induction(f: impl Into<Function>, init: Value) -> Rc<dyn ListLike> {
+    let f = f.into();
+    if !closed_forms() {
+        return Rc::new(InductionList::new(f, init));
+    }
+    match (f.step(), init) {
+        (Some(Step::Identity), init) => Rc::new(ConstantList::new(init)),
+        (Some(Step::Constant(k)), init) => Rc::new(ConstantList::after(init, Value::Number(k))),
+        (Some(Step::Add(step)), Value::Number(start)) => Rc::new(ProgressionList::new(start, step)),
+        (_, init) => Rc::new(InductionList::new(f, init)),
+    }
 }
 
 impl InductionList {
-    pub fn new(f: ParseTree, init: Value) -> InductionList {
+    pub fn new(f: impl Into<Function>, init: Value) -> InductionList {
+        InductionList::with_checkpoints(f, init, checkpoint_interval())
+    }
+
+    pub fn with_checkpoints(f: impl Into<Function>, init: Value, interval: usize) -> InductionList {
+        let function = f.into();
+        // recomputing elements would print their debugs again
+        let interval = if function.has_debug() { 1 } else { interval.max(1) };
         InductionList {
-            function: f,
-            initial_value: init,
-            resolved: RefCell::new(Vec::new()),
+            function,
+            resolved: RefCell::new(vec![init.clone()]),
+            interval,
+            latest: RefCell::new((0, init)),
+            fixed_point: RefCell::new(None),
+            map: None,
         }
     }
+
+    // how many elements are being held on to
+    pub fn stored(&self) -> usize {
+        self.resolved.borrow().len()
+    }
 }
 
 impl ListLike for InductionList {
     fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        match &self.map {
+            None => self.element(i),
+            // like a MapList over the induction, which needs a length for negative indexes
+            Some(_) if i < 0 => Err(RuntimeError::ResolvingInfiniteList(codes::INFINITE_LENGTH, String::from("Cannot get length of infinite list"))),
+            Some(f) => {
+                let element = self.element(i)?;
+                f.call_element("map", i, &element)
+            }
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::INFINITE_LENGTH, String::from("Cannot get length of infinite list")))
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::FORCING_INFINITE_LIST, "Attempted to force_resolve an infinite list. (Does your final output include one?)".to_owned()))
+    }
+
+    // The elements of the induction are computed as usual, but the mapped values aren't
+    // cached, since nothing with a debug is fused.
+    fn fuse_map(&self, f: &Function) -> Option<Rc<dyn ListLike>> {
+        let map = match &self.map {
+            None => f.clone(),
+            Some(g) => Function::compose(g.clone(), f.clone()),
+        };
+        Some(Rc::new(InductionList {
+            function: self.function.clone(),
+            resolved: RefCell::new(self.resolved.borrow().clone()),
+            interval: self.interval,
+            latest: RefCell::new(self.latest.borrow().clone()),
+            fixed_point: RefCell::new(self.fixed_point.borrow().clone()),
+            map: Some(map),
+        }))
+    }
+
+    // The elements being kept, before any fused map. Elements between checkpoints can be
+    // recomputed, so only the ones after the latest are thunks.
+    fn parts(&self) -> Parts {
+        let mut label = format!("of {}", self.function);
+        if self.interval > 1 {
+            label.push_str(&format!(", keeping 1 in {}", self.interval));
+        }
+        if let Some(map) = &self.map {
+            label.push_str(&format!(", then mapped with {}", map));
+        }
+        let mut children: Vec<(String, Part)> = self.resolved.borrow().iter()
+            .enumerate()
+            .map(|(i, v)| (format!("[{}]", i * self.interval), Part::Value(v.clone())))
+            .collect();
+        let (latest, value) = self.latest.borrow().clone();
+        if latest % self.interval != 0 {
+            children.push((format!("[{}]", latest), Part::Value(value)));
+        }
+        children.push((thunks(latest as i64 + 1, None), Part::Thunk));
+        if let Some(fixed_point) = self.fixed_point.borrow().clone() {
+            children.push((String::from("[-1]"), Part::Value(fixed_point)));
+        }
+        Parts { kind: "InductionList", label, children }
+    }
+}
+
+impl InductionList {
+    // the element of the induction itself, before any fused maps
+    fn element(&self, i: i64) -> Result<Value, RuntimeError> {
         if i >= 0 {
             let i = usize::try_from(i)
-                .map_err(|_| RuntimeError::OutOfBounds(format!("unknown error when indexing list (i = {})", i)))?;
+                .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!("unknown error when indexing list (i = {})", i)))?;
 
             let mut resolved = self.resolved.borrow_mut();
-            if resolved.len() == 0 {
-                resolved.push(self.initial_value.clone());
+            if i % self.interval == 0 && i / self.interval < resolved.len() {
+                return Ok(resolved[i / self.interval].clone());
             }
-            while i >= resolved.len() {
-                let prevresolved = resolved[resolved.len() - 1].clone();
-                resolved.push(evaluate::evaluate(&self.function, &prevresolved)?);
+
+            // start from the latest element if it comes before i, otherwise from the last
+            // checkpoint kept before i (a fixed point search can pass checkpoints without
+            // keeping them)
+            let mut latest = self.latest.borrow_mut();
+            let (mut n, mut value) = if latest.0 <= i {
+                latest.clone()
+            } else {
+                let checkpoint = (i / self.interval).min(resolved.len() - 1);
+                (checkpoint * self.interval, resolved[checkpoint].clone())
+            };
+            while n < i {
+                value = self.function.call_element("induction", n as i64 + 1, &value)?;
+                n += 1;
+                if n % self.interval == 0 && n / self.interval == resolved.len() {
+                    resolved.push(value.clone());
+                }
+                if n > latest.0 {
+                    *latest = (n, value.clone());
+                }
             }
-            Ok(resolved[i].clone())
+            Ok(value)
         }else{
             // negative indecies always return the first reached fixed point
-            // Err(RuntimeError::NegativeIndex(String::from("cannot negatively index infinite lists")))
+            // the search carries on from the latest element, and only needs the previous value
+            // to find it, so it runs in constant memory. Bodies with debugs keep every element
+            // like indexing does, so they aren't computed (and printed) twice.
+            if let Some(fixed_point) = self.fixed_point.borrow().clone() {
+                return Ok(fixed_point);
+            }
+            let keep = self.function.has_debug();
+            let mut resolved = self.resolved.borrow_mut();
+            let mut latest = self.latest.borrow_mut();
+            let (mut n, mut prevresolved) = latest.clone();
             loop {
-                let mut resolved = self.resolved.borrow_mut();
-                if resolved.len() == 0 {
-                    resolved.push(self.initial_value.clone());
+                // the element being searched for is the fixed point, at index -1
+                let nextresolved = self.function.call_element("induction", -1, &prevresolved)?;
+                n += 1;
+                if keep && n == resolved.len() {
+                    resolved.push(nextresolved.clone());
                 }
-                let prevresolved = resolved[resolved.len() - 1].clone();
-                let nextresolved = evaluate::evaluate(&self.function, &prevresolved)?;
+                *latest = (n, nextresolved.clone());
                 if prevresolved == nextresolved {
+                    *self.fixed_point.borrow_mut() = Some(nextresolved.clone());
                     return Ok(nextresolved);
-                }else{
-                    resolved.push(nextresolved);
                 }
+                prevresolved = nextresolved;
             }
         }
     }
-
-    fn length(&self) -> Result<i64, RuntimeError> {
-        return Err(RuntimeError::ResolvingInfiniteList(String::from("Cannot get length of infinite list")));
-    }
-
-    fn force_resolve(&self) -> Result<(), RuntimeError> {
-        Err(RuntimeError::ResolvingInfiniteList("Attempted to force_resolve an infinite list. (Does your final output include one?)".to_owned()))
-    }
 }
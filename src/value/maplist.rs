@@ -1,34 +1,155 @@
-use std::{cell::RefCell, rc::Rc};
+use std::cell::Cell;
 
-use super::{ListLike, Value};
+use super::{thunks, Function, ListLike, Part, Parts, Value};
 
-use crate::evaluate;
-use crate::errors::RuntimeError;
-use crate::parsetree::ParseTree;
+use crate::errors::{codes, RuntimeError};
+use crate::stats;
+use crate::sync::{RefCell, Rc};
+
+thread_local! {
+    static JOBS: Cell<usize> = const { Cell::new(1) };
+}
+
+// How many threads force_resolve spreads the elements of a map over. Only used with the
+// parallel feature, since values can't be sent between threads without it.
+pub fn set_jobs(jobs: usize) {
+    JOBS.with(|c| c.set(jobs.max(1)));
+}
 
 pub struct MapList {
-    function: ParseTree,
+    function: Function,
     source: Rc<dyn ListLike>,
     resolved: RefCell<Vec<Option<Value>>>,
 }
 
+// Builds the list l[f). If l is a map or an induction that isn't used anywhere else, f is
+// folded into it instead of putting another list with another cache on top, so X[f)[g)
+// becomes X[f then g).
+pub fn map(f: impl Into<Function>, l: Rc<dyn ListLike>) -> Rc<dyn ListLike> {
+    let f = f.into();
+    // calling f without caching its results could print its debugs more than once
+    if Rc::strong_count(&l) == 1 && !f.has_debug() {
+        if let Some(fused) = l.fuse_map(&f) {
+            stats::count_fused_map();
+            return fused;
+        }
+    }
+    Rc::new(MapList::new(f, l))
+}
+
 impl MapList {
-    pub fn new(f: ParseTree, s: Rc<dyn ListLike>) -> MapList {
+    pub fn new(f: impl Into<Function>, s: Rc<dyn ListLike>) -> MapList {
         MapList {
-            function: f,
+            function: f.into(),
             source: s,
             resolved: RefCell::new(Vec::new())
         }
     }
+
+    fn resolve(&self, i: i64, trueindex: usize) -> Result<Value, RuntimeError> {
+        let mut resolved = self.resolved.borrow_mut();
+        while resolved.len() <= trueindex{
+            resolved.push(None);
+        }
+
+        Ok(
+            match &resolved[trueindex] {
+                None => {
+                    let ans = self.source
+                            .index(i)
+                            .and_then(|v| self.function.call_element("map", i, &v))?;
+                    resolved[trueindex] = Some(ans.clone());
+                    ans
+                }
+                Some(ans) => ans.clone()
+            }
+        )
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl MapList {
+    // Computes the missing elements with thread t taking elements t, t + jobs, ... . The
+    // debug output of each element is kept and printed in index order afterwards, up to the
+    // first element that fails, so the output is the same as forcing them one at a time.
+    // Debugs of lists shared between elements are printed with whichever element forced
+    // them first.
+    fn force_parallel(&self, resolved: &mut [Option<Value>], jobs: usize) -> Result<(), RuntimeError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+
+        use super::inductionlist;
+        use crate::output;
+
+        let len = resolved.len();
+        let missing: Vec<bool> = resolved.iter().map(Option::is_none).collect();
+        let (interval, closed_forms) = (inductionlist::checkpoint_interval(), inductionlist::closed_forms());
+        let (settings, depth) = (output::settings(), output::depth());
+        // elements after one that failed don't need to be computed
+        let failed = AtomicUsize::new(usize::MAX);
+
+        let computed = thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs).map(|t| {
+                let (missing, failed) = (&missing, &failed);
+                scope.spawn(move || {
+                    inductionlist::set_checkpoint_interval(interval);
+                    inductionlist::set_closed_forms(closed_forms);
+                    output::configure(settings);
+                    output::set_depth(depth);
+                    let mut computed = Vec::new();
+                    for i in (t..len).step_by(jobs).filter(|&i| missing[i]) {
+                        if i > failed.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let (result, printed) = output::capture(|| self.source
+                            .index(i as i64)
+                            .and_then(|v| self.function.call_element("map", i as i64, &v)));
+                        if result.is_err() {
+                            failed.fetch_min(i, Ordering::Relaxed);
+                        }
+                        computed.push((i, result, printed));
+                    }
+                    (computed, stats::get())
+                })
+            }).collect();
+
+            let mut computed: Vec<_> = (0..len).map(|_| None).collect();
+            for worker in workers {
+                let (elements, counts) = worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e));
+                stats::add(counts);
+                for (i, result, printed) in elements {
+                    computed[i] = Some((result, printed));
+                }
+            }
+            computed
+        });
+
+        for (element, computed) in resolved.iter_mut().zip(computed) {
+            if let Some((result, printed)) = computed {
+                output::write(&printed);
+                *element = Some(result?);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ListLike for MapList {
     fn index(&self, i: i64) -> Result<Value, RuntimeError> {
         let trueindex;
-        let len = self.source.length()?;
+        let len = match self.source.length() {
+            Ok(len) => len,
+            // maps over infinite lists can still be indexed from the front
+            Err(RuntimeError::ResolvingInfiniteList(..)) if i >= 0 => {
+                let trueindex = usize::try_from(i)
+                    .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!("unknown error when indexing list (i = {})", i)))?;
+                return self.resolve(i, trueindex);
+            }
+            Err(e) => return Err(e),
+        };
 
         if i >= len || i < -len{
-            return Err(RuntimeError::OutOfBounds(format!(
+            return Err(RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!(
                 "Attempted to access index {} of list of length {}",
                 i,
                 len
@@ -40,48 +161,80 @@ impl ListLike for MapList {
         }
 
         let trueindex = usize::try_from(trueindex)
-            .map_err(|_| RuntimeError::OutOfBounds(format!("unknown error when indexing list (i = {})", i)))?;
-
-        let mut resolved = self.resolved.borrow_mut();
-        while resolved.len() <= trueindex{
-            resolved.push(None);
-        }
-
-        Ok(
-            match &resolved[trueindex] {
-                None => {
-                    let ans = self.source
-                            .index(i)
-                            .and_then(|v| evaluate::evaluate(&self.function, &v))?;
-                    resolved[trueindex] = Some(ans.clone());
-                    ans
-                }
-                Some(ans) => ans.clone()
-            }
-        )
+            .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!("unknown error when indexing list (i = {})", i)))?;
+        self.resolve(i, trueindex)
     }
 
     fn length(&self) -> Result<i64, RuntimeError> {
-        return self.source.length();
+        self.source.length()
     }
 
     fn force_resolve(&self) -> Result<(), RuntimeError> {
-        let len = self.source.length()?;
+        let len = match self.source.length() {
+            Ok(len) => len,
+            Err(e) => {
+                // gives the error for forcing an infinite list, if that's why there's no length
+                self.source.force_resolve()?;
+                return Err(e);
+            }
+        };
         self.source.force_resolve()?;
 
+        let len = usize::try_from(len)
+            .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!("unknown error when forcing list (length = {})", len)))?;
         let mut resolved = self.resolved.borrow_mut();
-        while resolved.len() <= len as usize{
+        while resolved.len() <= len{
             resolved.push(None);
         }
 
-        for i in 0..len{
-            let trueindex = usize::try_from(i)
-                .map_err(|_| RuntimeError::OutOfBounds(format!("unknown error when indexing list (i = {})", i)))?;
+        #[cfg(feature = "parallel")]
+        {
+            let jobs = JOBS.with(Cell::get);
+            if jobs > 1 && len > 1 {
+                return self.force_parallel(&mut resolved[..len], jobs);
+            }
+        }
 
-            resolved[trueindex] = Some(self.source
-                .index(i)
-                .and_then(|v| evaluate::evaluate(&self.function, &v))?);
+        for (i, element) in resolved.iter_mut().enumerate().take(len){
+            if element.is_none() {
+                *element = Some(self.source
+                    .index(i as i64)
+                    .and_then(|v| self.function.call_element("map", i as i64, &v))?);
+            }
         }
         Ok(())
     }
+
+    fn fuse_map(&self, f: &Function) -> Option<Rc<dyn ListLike>> {
+        if self.function.has_debug() {
+            return None;
+        }
+        Some(Rc::new(MapList::new(Function::compose(self.function.clone(), f.clone()), self.source.clone())))
+    }
+
+    // runs of elements that haven't been computed are one thunk
+    fn parts(&self) -> Parts {
+        let mut children = vec![(String::from("source"), Part::Value(Value::List(self.source.clone())))];
+        let resolved = self.resolved.borrow();
+        let mut first_thunk = None;
+        for (i, element) in resolved.iter().enumerate() {
+            match (element, first_thunk) {
+                (Some(v), _) => {
+                    if let Some(first) = first_thunk.take() {
+                        children.push((thunks(first, Some(i as i64)), Part::Thunk));
+                    }
+                    children.push((format!("[{}]", i), Part::Value(v.clone())));
+                }
+                (None, None) => first_thunk = Some(i as i64),
+                (None, Some(_)) => {}
+            }
+        }
+        let first = first_thunk.unwrap_or(resolved.len() as i64);
+        match self.source.length() {
+            Ok(len) if len <= first => {}
+            Ok(len) => children.push((thunks(first, Some(len)), Part::Thunk)),
+            Err(_) => children.push((thunks(first, None), Part::Thunk)),
+        }
+        Parts { kind: "MapList", label: format!("of {}", self.function), children }
+    }
 }
@@ -1,7 +1,7 @@
 use std::fmt;
-use std::rc::Rc;
 
 use super::errors::RuntimeError;
+use super::sync::{Rc, Shareable};
 
 pub mod string;
 pub mod exactlist;
@@ -9,13 +9,22 @@ pub mod inductionlist;
 pub mod maplist;
 pub mod concatlist;
 pub mod encapsulatelist;
+pub mod progressionlist;
+pub mod constantlist;
+pub mod stringlist;
+pub mod function;
 pub use exactlist::ExactList;
 pub use inductionlist::InductionList;
 pub use maplist::MapList;
 pub use concatlist::ConcatList;
 pub use encapsulatelist::EncapsulateList;
+pub use progressionlist::ProgressionList;
+pub use constantlist::ConstantList;
+pub use stringlist::StringList;
+pub use function::{Function, Step};
 
-const INFINITE_LIST_PREVIEW_LENGTH: i64 = 3;
+// how many elements of an infinite list are shown before the ...
+pub(crate) const INFINITE_LIST_PREVIEW_LENGTH: i64 = 3;
 
 #[derive(Clone)]
 pub enum Value {
@@ -23,10 +32,62 @@ pub enum Value {
     List(Rc<dyn ListLike>),
 }
 
-pub trait ListLike {
+pub trait ListLike: Shareable {
     fn index(&self, i: i64) -> Result<Value, RuntimeError>;
     fn length(&self) -> Result<i64, RuntimeError>;
     fn force_resolve(&self) -> Result<(), RuntimeError>;
+
+    // lets concatenations look inside the lists they are built from
+    fn as_concat(&self) -> Option<&ConcatList> {
+        None
+    }
+
+    // the code points of lists that are stored as a string
+    fn as_chars(&self) -> Option<&[char]> {
+        None
+    }
+
+    // lets concatenations merge short strings
+    fn as_string(&self) -> Option<&StringList> {
+        None
+    }
+
+    // the elements of lists that already have all of them
+    fn as_values(&self) -> Option<&[Value]> {
+        None
+    }
+
+    // The list of f applied to every element, for lists that can build it without a separate
+    // MapList and its cache. Only called on lists that nothing else refers to, so the list
+    // itself can be thrown away afterwards.
+    fn fuse_map(&self, _f: &Function) -> Option<Rc<dyn ListLike>> {
+        None
+    }
+
+    // what the list is holding on to, without computing anything, see emit::dot
+    fn parts(&self) -> Parts;
+}
+
+// A list's type, a description of it and the values it refers to, each with what it is to
+// the list (like "[2]" for an element). Elements that haven't been computed yet are thunks.
+pub struct Parts {
+    pub kind: &'static str,
+    pub label: String,
+    pub children: Vec<(String, Part)>,
+}
+
+pub enum Part {
+    Value(Value),
+    Thunk,
+}
+
+// the name of a run of uncomputed elements, from first up to but not including end
+fn thunks(first: i64, end: Option<i64>) -> String {
+    match end {
+        Some(end) if end == first + 1 => format!("[{}]", first),
+        Some(end) => format!("[{}..{}]", first, end - 1),
+        None => format!("[{}..]", first),
+    }
 }
 
 impl Value {
@@ -39,8 +100,16 @@ impl Value {
         match self {
             Value::Number(n) => s.push_str(&format!("{}", n)[..]),
             Value::List(ll) => {
+                // strings can be written out without going through index
+                if let Some(chars) = ll.as_chars() {
+                    let codes: Vec<String> = chars.iter().map(|c| u32::from(*c).to_string()).collect();
+                    s.push('[');
+                    s.push_str(&codes.join(", "));
+                    s.push(']');
+                    return Ok(());
+                }
                 let (len, is_inf) = match ll.length() {
-                    Err(RuntimeError::ResolvingInfiniteList(_)) => (INFINITE_LIST_PREVIEW_LENGTH, true),
+                    Err(RuntimeError::ResolvingInfiniteList(..)) => (INFINITE_LIST_PREVIEW_LENGTH, true),
                     Ok(len) => (len, false),
                     Err(e) => return Err(e)
                 };
@@ -112,7 +181,8 @@ fn list_eq_helper(l1rc: &Rc<dyn ListLike>, l2rc: &Rc<dyn ListLike>) -> Result<bo
 
 #[cfg(test)]
 mod tests {
-    use crate::parsetree::ParseTree;
+    use crate::parsetree::{parse, ParseTree};
+    use crate::{evaluate, vm};
     use super::*;
 
     #[test]
@@ -192,13 +262,13 @@ mod tests {
     #[test]
     fn advanced_format_test() {
         let a = Value::List(Rc::new(InductionList::new(
-            ParseTree::EmptyList{line: 0},
+            ParseTree::EmptyList{line: 0, col: 0},
             Value::Number(0),
         )));
         assert_eq!(format!("{}", a), "[0, [], [], ...]");
 
         let a = Value::List(Rc::new(MapList::new(
-            ParseTree::Addition{arg1: Box::new(ParseTree::Input{line: 0}), arg2: Box::new(ParseTree::Input{line: 0}), line: 0},
+            ParseTree::Addition{arg1: Rc::new(ParseTree::Input{line: 0, col: 0}), arg2: Rc::new(ParseTree::Input{line: 0, col: 0}), line: 0, col: 0},
             Rc::new(ExactList::new(vec![Value::Number(1), Value::Number(2)])),
         )));
         assert_eq!(format!("{}", a), "[2, 4]");
@@ -208,7 +278,7 @@ mod tests {
     fn nested_format_test() {
         let a = Value::List(Rc::new(ExactList::new(vec![
             Value::List(Rc::new(InductionList::new(
-                ParseTree::EmptyList{line: 0},
+                ParseTree::EmptyList{line: 0, col: 0},
                 Value::Number(0),
             ))),
             Value::List(Rc::new(ExactList::new(vec![
@@ -226,19 +296,19 @@ mod tests {
     #[test]
     fn invalid_format_test() {
         let a = Value::List(Rc::new(MapList::new(
-            ParseTree::Addition{arg1: Box::new(ParseTree::Input{line: 0}), arg2: Box::new(ParseTree::EmptyList{line: 0}), line: 0},
+            ParseTree::Addition{arg1: Rc::new(ParseTree::Input{line: 0, col: 0}), arg2: Rc::new(ParseTree::EmptyList{line: 0, col: 0}), line: 0, col: 0},
             Rc::new(ExactList::new(vec![Value::Number(0), Value::Number(1)])),
         )));
 
         assert!(a.to_string().is_err());
         assert_eq!(format!("{}", a), "Mismatched Types - Cannot add number and list (line 0)");
-        assert_eq!(format!("{:?}", a), "MismatchedTypes(\"Cannot add number and list (line 0)\")");
+        assert_eq!(format!("{:?}", a), "MismatchedTypes(\"LM0105\", \"Cannot add number and list (line 0)\")");
     }
 
     #[test]
     fn map_error_test() {
         let a = MapList::new(
-            ParseTree::Addition{arg1:Box::new(ParseTree::Input{line: 0}), arg2:Box::new(ParseTree::EmptyList{line: 0}), line: 0},
+            ParseTree::Addition{arg1:Rc::new(ParseTree::Input{line: 0, col: 0}), arg2:Rc::new(ParseTree::EmptyList{line: 0, col: 0}), line: 0, col: 0},
             Rc::new(ExactList::new(vec![Value::Number(0), Value::Number(1)])),
         );
         assert!(a.index(0).is_err());
@@ -247,7 +317,7 @@ mod tests {
     #[test]
     fn simple_encapsulate_test() {
         let a = EncapsulateList::new(
-            ParseTree::Number { n: 5, line: 0 },
+            ParseTree::Number { n: 5, line: 0, col: 0},
             Value::Number(0)
         );
         assert_eq!(a.length().expect("length error"), 1);
@@ -264,9 +334,9 @@ mod tests {
     fn invalid_encapsulate_test() {
         let a = EncapsulateList::new(
             ParseTree::Addition {
-                arg1: Box::new(ParseTree::Number { n: 0, line: 0 }),
-                arg2: Box::new(ParseTree::EmptyList { line: 0 }),
-                line: 0
+                arg1: Rc::new(ParseTree::Number { n: 0, line: 0, col: 0}),
+                arg2: Rc::new(ParseTree::EmptyList { line: 0, col: 0}),
+                line: 0, col: 0
             },
             Value::Number(0)
         );
@@ -275,4 +345,235 @@ mod tests {
         assert!(a.force_resolve().is_err());
         assert!(a.index(0).is_err());
     }
+
+    fn run_both(expr: &str) -> Vec<String> {
+        let pt = parse(expr).expect("parse error");
+        let program = Rc::new(vm::compile(&pt));
+        [evaluate::evaluate(&pt, &Value::Number(0)), vm::run(&program, 0, &Value::Number(0))]
+            .into_iter()
+            .map(|result| match result {
+                Ok(v) => format!("{}", v),
+                Err(e) => String::from(e.code()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn closed_form_inductions() {
+        // these would take far too long one element at a time
+        assert_eq!(run_both("0(()(1)][1000000000000]"), vec!["1000000000000"; 2]);
+        assert_eq!(run_both("5(3(())][1000000000000]"), vec!["3000000000005"; 2]);
+        assert_eq!(run_both("5(()[2]][1000000000000]"), vec!["-1999999999995"; 2]);
+        assert_eq!(run_both("4[](()][1000000000000]"), vec!["[4]"; 2]);
+        assert_eq!(run_both("4[](()][0[1]]"), vec!["[4]"; 2]);
+        assert_eq!(run_both("7(()(0)][0[1]]"), vec!["7"; 2]);
+        assert_eq!(run_both("0(()(1)][0[1]]"), vec![crate::errors::codes::NO_FIXED_POINT; 2]);
+        assert_eq!(run_both("0(()(1)]"), vec!["[0, 1, 2, ...]"; 2]);
+    }
+
+    #[test]
+    fn closed_form_overflow() {
+        // step * i overflowing on its own doesn't matter when the element fits
+        assert_eq!(run_both("0[9223372036854775807][1](()(2)][4611686018427387904]"), vec!["0"; 2]);
+        // but the element not fitting does, like it would one element at a time
+        assert_eq!(std::panic::catch_unwind(|| run_both("9223372036854775806(()(1)][5]")).is_err(), cfg!(debug_assertions));
+    }
+
+    #[test]
+    fn closed_form_fallbacks() {
+        // ()(1) only counts when it starts on a number
+        assert_eq!(run_both("[](()(1)][0]"), vec!["[]"; 2]);
+        assert_eq!(run_both("[](()(1)][1]"), vec![crate::errors::codes::ADD_NUMBER_AND_LIST; 2]);
+        assert_eq!(run_both("1(()(())][3]"), vec!["8"; 2]);
+    }
+
+    #[test]
+    fn function_steps() {
+        for (expr, step) in [("()", Some(Step::Identity)), ("()(3)", Some(Step::Add(3))), ("3(())", Some(Step::Add(3))),
+                             ("()[3]", Some(Step::Add(-3))), ("3", Some(Step::Constant(3))), ("()(1)(1)", None), ("()!", None)] {
+            let pt = Rc::new(parse(expr).expect("parse error"));
+            assert_eq!(Function::Tree(pt.clone()).step(), step, "{}", expr);
+            assert_eq!(Function::Code(Rc::new(vm::compile(&pt)), 0).step(), step, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn constant_body_induction() {
+        assert_eq!(run_both("1(0][1000000000000]"), vec!["0"; 2]);
+        assert_eq!(run_both("1(0][0]"), vec!["1"; 2]);
+        assert_eq!(run_both("1(0][0[1]]"), vec!["0"; 2]);
+        assert_eq!(run_both("[](0][0[1]]"), vec!["0"; 2]);
+    }
+
+    #[test]
+    fn induction_checkpoints() {
+        // doubles its input, which is too complicated for a closed form
+        let body = Rc::new(parse("()(())").expect("parse error"));
+        let all = InductionList::with_checkpoints(body.clone(), Value::Number(1), 1);
+        let some = InductionList::with_checkpoints(body.clone(), Value::Number(1), 10);
+        for i in [40, 3, 25, 0, 39, 11, 62, 62, 20] {
+            assert!(all.index(i).expect("index error") == some.index(i).expect("index error"), "{}", i);
+        }
+        assert_eq!(all.stored(), 63);
+        assert_eq!(some.stored(), 7);
+
+        // debugs would print again, so every element is kept
+        let body = Rc::new(parse("()!(())").expect("parse error"));
+        let debug = InductionList::with_checkpoints(body, Value::Number(1), 10);
+        debug.index(5).expect("index error");
+        assert_eq!(debug.stored(), 6);
+    }
+
+    #[test]
+    fn fixed_point_search() {
+        // counts down to 0 and stays there
+        let body = Rc::new(parse("()[1[1(0][()]]]").expect("parse error"));
+        let list = InductionList::with_checkpoints(body, Value::Number(100), 10);
+        assert!(list.index(-1).expect("index error") == Value::Number(0));
+        // nothing is kept on the way, but indexing can still pick up from the start
+        assert_eq!(list.stored(), 1);
+        assert!(list.index(55).expect("index error") == Value::Number(45));
+        assert!(list.index(150).expect("index error") == Value::Number(0));
+
+        // the elements found by the search are kept, so their debugs don't print again
+        let pt = parse("3(()![1[1(0][()]]]][][()[0[1]](()[2]))[0]").expect("parse error");
+        let (result, printed) = crate::output::capture(|| evaluate::evaluate(&pt, &Value::Number(0)));
+        assert!(result.expect("runtime error") == Value::Number(1));
+        assert_eq!(printed, "Debug at 1:5 - 3\nDebug at 1:5 - 2\nDebug at 1:5 - 1\nDebug at 1:5 - 0\n");
+    }
+
+    fn encapsulated(n: i64) -> Rc<dyn ListLike> {
+        Rc::new(EncapsulateList::new(ParseTree::Number{n, line: 0, col: 0}, Value::Number(0)))
+    }
+
+    #[test]
+    fn balanced_concat() {
+        let mut list: Rc<dyn ListLike> = Rc::new(ExactList::new(vec![]));
+        for n in 0..10000 {
+            list = ConcatList::concat(list, encapsulated(n));
+        }
+        assert_eq!(list.length().expect("length error"), 10000);
+        for i in [0, 1, 5000, 9999] {
+            assert!(list.index(i).expect("index error") == Value::Number(i));
+        }
+        // indexes out of range go to the first and last lists joined, like before balancing
+        assert!(list.index(-1).is_err());
+        let pair = ConcatList::concat(encapsulated(1), encapsulated(2));
+        assert!(pair.index(-1).expect("index error") == Value::Number(1));
+        assert_eq!(list.index(10000).err().map(|e| e.code()), Some(crate::errors::codes::ENCAPSULATE_OUT_OF_BOUNDS));
+        // an AVL tree with n leaves is at most about 1.44 log2(n) high
+        let height = list.as_concat().expect("not a concatenation").height();
+        assert!(height <= 20, "height {}", height);
+
+        // building from the other side, and joining two big lists
+        let mut front: Rc<dyn ListLike> = Rc::new(ExactList::new(vec![]));
+        for n in (0..1000).rev() {
+            front = ConcatList::concat(encapsulated(n), front);
+        }
+        let both = ConcatList::concat(front, list);
+        assert_eq!(both.length().expect("length error"), 11000);
+        assert!(both.index(999).expect("index error") == Value::Number(999));
+        assert!(both.index(1000).expect("index error") == Value::Number(0));
+    }
+
+    #[test]
+    fn infinite_concat() {
+        let naturals = Rc::new(InductionList::new(
+            parse("()(())(1)").expect("parse error"),
+            Value::Number(0),
+        ));
+        let list = ConcatList::concat(encapsulated(5), naturals.clone());
+        assert!(list.index(2).expect("index error") == Value::Number(1));
+        assert!(list.length().is_err());
+        // nothing after an infinite list can be reached
+        let list = ConcatList::concat(naturals, encapsulated(5));
+        assert!(list.index(2).expect("index error") == Value::Number(3));
+    }
+
+    #[test]
+    fn string_lists() {
+        let hi = string::string_to_list("hi").unwrap_or(Value::Number(0));
+        assert_eq!(format!("{}", hi), "[104, 105]");
+        let Value::List(hi) = hi else { panic!("Bad return type") };
+        assert!(hi.index(-1).expect("index error") == Value::Number(105));
+        assert!(hi.index(2).is_err());
+        assert!(Value::List(hi.clone()) == Value::List(Rc::new(ExactList::new(vec![Value::Number(104), Value::Number(105)]))));
+
+        // short strings are merged, long ones are joined
+        let hihi = ConcatList::concat(hi.clone(), hi.clone());
+        assert_eq!(hihi.as_chars(), Some(&['h', 'i', 'h', 'i'][..]));
+        // but indexes out of range still go to the first or last string
+        let Ok(Value::List(ya)) = string::string_to_list("ya") else { panic!("Bad return type") };
+        let hiya = ConcatList::concat(hihi, ya);
+        assert!(hiya.index(-1).expect("index error") == Value::Number(105));
+        assert!(hiya.index(5).expect("index error") == Value::Number(97));
+        assert_eq!(hiya.index(-3).err().map(|e| e.to_string()), Some(String::from("Out of Bounds Error - Attempted to access index -3 of list of length 2")));
+        assert_eq!(hiya.index(7).err().map(|e| e.to_string()), Some(String::from("Out of Bounds Error - Attempted to access index 3 of list of length 2")));
+        let long = string::string_to_list(&"a".repeat(5000)).unwrap_or(Value::Number(0));
+        let Value::List(long) = long else { panic!("Bad return type") };
+        let joined = ConcatList::concat(long, hi.clone());
+        assert!(joined.as_chars().is_none());
+        let joined = string::list_to_string(&Value::List(joined)).unwrap_or_default();
+        assert_eq!(joined.len(), 5002);
+        assert!(joined.ends_with("aahi"));
+
+        // and anything else is still converted element by element
+        let mixed = ConcatList::concat(hi, encapsulated(33));
+        assert_eq!(string::list_to_string(&Value::List(mixed)).unwrap_or_default(), "hi!");
+    }
+
+    #[test]
+    fn fused_maps() {
+        crate::stats::reset();
+        assert_eq!(run_both("1[](2[])[()(1))[()(()))"), vec!["[4, 6]"; 2]);
+        // counted once for each backend
+        assert_eq!(crate::stats::get().fused_maps, 2);
+
+        // maps over inductions can be indexed from the front, fused or not
+        crate::stats::reset();
+        assert_eq!(run_both("0(()(())(1)][()(1))[()(()))[3]"), vec!["16"; 2]);
+        assert_eq!(run_both("0(()(())(1)][()(1))[3]"), vec!["8"; 2]);
+        assert_eq!(run_both("0(()(1)][()(1))[3]"), vec!["4"; 2]);
+        assert_eq!(run_both("0(()(())(1)][()(1))[0[1]]"), vec![crate::errors::codes::INFINITE_LENGTH; 2]);
+        assert_eq!(run_both("0(()(())(1)][()(1))"), vec!["[1, 2, 4, ...]"; 2]);
+        assert_eq!(crate::stats::get().fused_maps, 10);
+
+        // debugs aren't fused, and a list used twice keeps its own cache
+        crate::stats::reset();
+        assert_eq!(run_both("1[](2[])[()!(1))[()(()))"), vec!["[4, 6]"; 2]);
+        assert_eq!(run_both("1[](2[])[()(1))[()(())!)"), vec!["[4, 6]"; 2]);
+        assert_eq!(crate::stats::get().fused_maps, 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_maps() {
+        use crate::output;
+
+        fn forced(expr: &str, jobs: usize) -> (String, String) {
+            maplist::set_jobs(jobs);
+            let pt = parse(expr).expect("parse error");
+            let (result, printed) = output::capture(|| evaluate::evaluate(&pt, &Value::Number(0))
+                .and_then(|v| { v.force_resolve()?; Ok(v) }));
+            maplist::set_jobs(1);
+            match result {
+                Ok(v) => (format!("{}", v), printed),
+                Err(e) => (String::from(e.code()), printed),
+            }
+        }
+
+        for expr in [
+            "1[](2[])(3[])(4[])(5[])(6[])(7[])[()!(()))",
+            "1[](2[])(3[])(4[])(5[])[0(()!(1)][()]!)",
+            // elements after the first failure don't print anything
+            "1[](2[])(0[][])(4[])(5[])[()!(1))",
+            "1[](0[][])(0[][])(4[])[()!(1))",
+        ] {
+            let expected = forced(expr, 1);
+            for jobs in [2, 3, 8] {
+                assert_eq!(forced(expr, jobs), expected, "{} with {} jobs", expr, jobs);
+            }
+        }
+        assert_eq!(forced("1[](2[])(0[][])(4[])(5[])[()!(1))", 4).1.lines().count(), 3);
+    }
 }
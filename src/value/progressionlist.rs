@@ -0,0 +1,59 @@
+use super::{ListLike, Parts, Value};
+
+use crate::errors::{codes, RuntimeError};
+
+// The induction start(()(step)], computed directly instead of one element at a time.
+// Elements that don't fit in an i64 overflow the way the additions in evaluate would on the
+// way there, panicking in debug builds and wrapping around in release builds.
+pub struct ProgressionList {
+    start: i64,
+    step: i64,
+}
+
+impl ProgressionList {
+    pub fn new(start: i64, step: i64) -> ProgressionList {
+        ProgressionList { start, step }
+    }
+}
+
+// start + step * i, which only overflows when the element itself doesn't fit, since every
+// element before it lies between start and it
+fn element(start: i64, step: i64, i: i64) -> i64 {
+    let n = start as i128 + step as i128 * i as i128;
+    match i64::try_from(n) {
+        Ok(n) => n,
+        Err(_) if cfg!(debug_assertions) => panic!("attempt to add with overflow"),
+        Err(_) => n as i64,
+    }
+}
+
+impl ListLike for ProgressionList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        if i >= 0 {
+            Ok(Value::Number(element(self.start, self.step, i)))
+        } else if self.step == 0 {
+            Ok(Value::Number(self.start))
+        } else {
+            Err(RuntimeError::ResolvingInfiniteList(codes::NO_FIXED_POINT, format!(
+                "Searched for the fixed point of a list that counts up by {} forever",
+                self.step
+            )))
+        }
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::INFINITE_LENGTH, String::from("Cannot get length of infinite list")))
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Err(RuntimeError::ResolvingInfiniteList(codes::FORCING_INFINITE_LIST, "Attempted to force_resolve an infinite list. (Does your final output include one?)".to_owned()))
+    }
+
+    fn parts(&self) -> Parts {
+        Parts {
+            kind: "ProgressionList",
+            label: format!("{} then {} more each time", self.start, self.step),
+            children: Vec::new(),
+        }
+    }
+}
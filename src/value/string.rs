@@ -1,36 +1,47 @@
-use std::rc::Rc;
+use super::{ListLike, Value};
+use super::stringlist::StringList;
 
-use super::Value;
-use super::exactlist::ExactList;
+use crate::sync::Rc;
 
 pub struct StringError;
 
-pub fn string_to_list(s: &String) -> Result<Value, StringError> {
-    let mut contents = Vec::new();
-    for c in s.chars() {
-        contents.push(Value::Number(
-            i64::try_from(u32::try_from(c).map_err(|_| StringError)?).map_err(|_| StringError)?,
-        ))
-    }
-    let exact = ExactList::new(contents);
-    Ok(Value::List(Rc::new(exact)))
+pub fn string_to_list(s: &str) -> Result<Value, StringError> {
+    Ok(Value::List(Rc::new(StringList::new(s.chars().collect()))))
 }
 
 pub fn list_to_string(v: &Value) -> Result<String, StringError> {
     match v {
         Value::List(ll) => {
+            // infinite lists aren't strings, and this rules out any infinite parts in concatenations
+            ll.length().map_err(|_| StringError)?;
             let mut ans = String::new();
-            for i in 0..ll.length().map_err(|_| StringError)? {
-                ans.push(match ll.index(i).map_err(|_| StringError)? {
-                    Value::Number(n) => char::try_from(u32::try_from(n).map_err(|_| StringError)?)
-                        .map_err(|_| StringError)?,
-                    _ => {
-                        return Err(StringError);
-                    }
-                })
-            }
+            push_chars(&**ll, &mut ans)?;
             Ok(ans)
         }
         _ => Err(StringError),
     }
 }
+
+// Appends the characters of a finite list. Strings and concatenations of them are copied
+// directly, everything else is indexed one element at a time.
+fn push_chars(ll: &dyn ListLike, ans: &mut String) -> Result<(), StringError> {
+    if let Some(chars) = ll.as_chars() {
+        ans.extend(chars);
+        return Ok(());
+    }
+    if let Some(concat) = ll.as_concat() {
+        let (first, second) = concat.sides();
+        push_chars(&**first, ans)?;
+        return push_chars(&**second, ans);
+    }
+    for i in 0..ll.length().map_err(|_| StringError)? {
+        ans.push(match ll.index(i).map_err(|_| StringError)? {
+            Value::Number(n) => char::try_from(u32::try_from(n).map_err(|_| StringError)?)
+                .map_err(|_| StringError)?,
+            _ => {
+                return Err(StringError);
+            }
+        })
+    }
+    Ok(())
+}
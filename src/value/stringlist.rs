@@ -0,0 +1,75 @@
+use super::{ListLike, Parts, Value};
+
+use crate::errors::{codes, RuntimeError};
+
+// A list of unicode code points stored next to each other, which is how string inputs
+// come in. It behaves exactly like an ExactList of the same numbers, or when it was joined
+// from shorter strings, like the ConcatList of them.
+pub struct StringList {
+    chars: Vec<char>,
+    // the lengths of the first and last strings joined, which is where a ConcatList sends
+    // negative indexes and indexes past the end
+    first: usize,
+    last: usize,
+}
+
+impl StringList {
+    pub fn new(chars: Vec<char>) -> StringList {
+        let len = chars.len();
+        StringList { chars, first: len, last: len }
+    }
+
+    pub fn join(s1: &StringList, s2: &StringList) -> StringList {
+        StringList {
+            chars: [&s1.chars[..], &s2.chars[..]].concat(),
+            first: s1.first,
+            last: s2.last,
+        }
+    }
+}
+
+impl ListLike for StringList {
+    fn index(&self, i: i64) -> Result<Value, RuntimeError> {
+        let len = self.length()?;
+        let (first, last) = (self.first as i64, self.last as i64);
+        let trueindex = if i >= 0 && i < len {
+            i
+        } else if i < 0 && i >= -first {
+            first + i
+        } else {
+            // reported the way the first or last string would report it
+            let (i, len) = if i < 0 { (i, first) } else { (i - (len - last), last) };
+            return Err(RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, format!(
+                "Attempted to access index {} of list of length {}",
+                i,
+                len
+            )));
+        };
+        Ok(Value::Number(i64::from(u32::from(self.chars[trueindex as usize]))))
+    }
+
+    fn length(&self) -> Result<i64, RuntimeError> {
+        i64::try_from(self.chars.len())
+            .map_err(|_| RuntimeError::OutOfBounds(codes::FINITE_OUT_OF_BOUNDS, String::from("length could not be converted to i64")))
+    }
+
+    fn force_resolve(&self) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    fn as_chars(&self) -> Option<&[char]> {
+        Some(&self.chars)
+    }
+
+    fn as_string(&self) -> Option<&StringList> {
+        Some(self)
+    }
+
+    fn parts(&self) -> Parts {
+        Parts {
+            kind: "StringList",
+            label: self.chars.iter().collect(),
+            children: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use super::{Instruction, Program};
+
+use crate::parsetree::ParseTree;
+
+pub fn compile(expression: &ParseTree) -> Program {
+    let mut program = Program { functions: vec![Vec::new()] };
+    compile_into(expression, 0, &mut program);
+    program
+}
+
+// creates a new function in the program from a body and returns its index
+fn compile_function(body: &ParseTree, program: &mut Program) -> usize {
+    program.functions.push(Vec::new());
+    let f = program.functions.len() - 1;
+    compile_into(body, f, program);
+    f
+}
+
+// appends the instructions for expression to the end of function f
+fn compile_into(expression: &ParseTree, f: usize, program: &mut Program) {
+    let instruction = match expression {
+        ParseTree::Number{n, ..} => Instruction::Number(*n),
+
+        ParseTree::Input{..} => Instruction::Input,
+
+        ParseTree::EmptyList{..} => Instruction::EmptyList,
+
+        ParseTree::Length{arg, ..} => {
+            compile_into(arg, f, program);
+            Instruction::Length
+        }
+
+        ParseTree::Encapsulate{arg, ..} => Instruction::Encapsulate(compile_function(arg, program)),
+
+        ParseTree::Strict{arg, ..} => {
+            compile_into(arg, f, program);
+            Instruction::Singleton
+        }
+
+        ParseTree::Addition{arg1, arg2, line, ..} => {
+            compile_into(arg1, f, program);
+            compile_into(arg2, f, program);
+            Instruction::Addition{line: *line}
+        }
+
+        ParseTree::IndexSubtraction{arg1, arg2, line, ..} => {
+            compile_into(arg1, f, program);
+            compile_into(arg2, f, program);
+            Instruction::IndexSubtraction{line: *line}
+        }
+
+        ParseTree::Induction{arg1, arg2, ..} => {
+            compile_into(arg1, f, program);
+            Instruction::Induction(compile_function(arg2, program))
+        }
+
+        ParseTree::Map{arg1, arg2, line, ..} => {
+            compile_into(arg1, f, program);
+            Instruction::Map{function: compile_function(arg2, program), line: *line}
+        }
+
+        ParseTree::Debug{arg, line, col} => {
+            compile_into(arg, f, program);
+            Instruction::Debug{line: *line, col: *col}
+        }
+
+        ParseTree::Shared{arg, slot} => {
+            let load = program.functions[f].len();
+            program.functions[f].push(Instruction::LoadShared{slot: *slot, skip: 0});
+            compile_into(arg, f, program);
+            let skip = program.functions[f].len() - load;
+            program.functions[f][load] = Instruction::LoadShared{slot: *slot, skip};
+            Instruction::StoreShared(*slot)
+        }
+    };
+    program.functions[f].push(instruction);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsetree::parse;
+
+    #[test]
+    fn compile_shared() {
+        let pt = crate::cse::share(&crate::sync::Rc::new(parse("()()(()())").expect("parse error")));
+        assert_eq!(
+            compile(&pt).functions[0],
+            vec![
+                Instruction::LoadShared{slot: 0, skip: 3},
+                Instruction::Input,
+                Instruction::Length,
+                Instruction::StoreShared(0),
+                Instruction::LoadShared{slot: 0, skip: 3},
+                Instruction::Input,
+                Instruction::Length,
+                Instruction::StoreShared(0),
+                Instruction::Addition{line: 1},
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_functions() {
+        let pt = parse("1(2)[()[](3[]))").expect("parse error");
+        assert_eq!(
+            compile(&pt),
+            Program { functions: vec![
+                vec![
+                    Instruction::Number(1),
+                    Instruction::Number(2),
+                    Instruction::Addition{line: 1},
+                    Instruction::Map{function: 1, line: 1},
+                ],
+                vec![
+                    Instruction::Encapsulate(2),
+                    Instruction::Encapsulate(3),
+                    Instruction::Addition{line: 1},
+                ],
+                vec![Instruction::Input],
+                vec![Instruction::Number(3)],
+            ]}
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use super::errors::{codes, RuntimeError};
+use super::observer;
+use super::stats;
+use super::sync::Rc;
+use super::value::{self, inductionlist, maplist, ConcatList, EncapsulateList, Function, Value};
+
+pub mod compile;
+pub use compile::compile;
+
+// Instructions for a stack machine. Each one pops its arguments (the right one on top)
+// and pushes its result. Function bodies are referred to by their index in the Program.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Instruction {
+    Number(i64),
+    Input,
+    EmptyList,
+    Length,
+    Encapsulate(usize),
+    // a list of just the value on top of the stack
+    Singleton,
+    Addition{line: u32},
+    IndexSubtraction{line: u32},
+    Induction(usize),
+    Map{function: usize, line: u32},
+    Debug{line: u32, col: u32},
+    // pushes the value of a shared subexpression and jumps past the skip instructions that
+    // compute it, if it has already been computed
+    LoadShared{slot: usize, skip: usize},
+    // saves the value on top of the stack without popping it
+    StoreShared(usize),
+}
+
+// functions[0] is the whole program, the rest are the bodies of maps, inductions and encapsulations
+#[derive(Debug, PartialEq, Eq)]
+pub struct Program {
+    pub functions: Vec<Vec<Instruction>>,
+}
+
+pub fn run(program: &Rc<Program>, function: usize, input: &Value) -> Result<Value, RuntimeError> {
+    let instructions = &program.functions[function];
+    let mut stack: Vec<Value> = Vec::new();
+    // the values of the ParseTree::Shared slots, see cse::share
+    let mut shared: Vec<Option<Value>> = Vec::new();
+    let mut pc = 0;
+    while pc < instructions.len() {
+        let instruction = &instructions[pc];
+        pc += 1;
+        let result = match instruction {
+            Instruction::Number(n) => Value::Number(*n),
+
+            Instruction::Input => input.clone(),
+
+            Instruction::EmptyList => Value::List(Rc::new(value::ExactList::new(Vec::new()))),
+
+            Instruction::Length => match pop(&mut stack) {
+                Value::Number(n) => Value::Number(n.abs()),
+                Value::List(l) => Value::Number(l.length()?),
+            },
+
+            Instruction::Encapsulate(f) => Value::List(Rc::new(EncapsulateList::new(
+                Function::Code(program.clone(), *f),
+                input.clone(),
+            ))),
+
+            Instruction::Singleton => Value::List(Rc::new(EncapsulateList::evaluated(pop(&mut stack)))),
+
+            Instruction::Addition{line} => {
+                let arg2 = pop(&mut stack);
+                match (pop(&mut stack), arg2) {
+                    (Value::Number(n1), Value::Number(n2)) => Value::Number(n1 + n2),
+                    (Value::List(l1), Value::List(l2)) => Value::List(ConcatList::concat(l1, l2)),
+                    _ => return Err(RuntimeError::MismatchedTypes(codes::ADD_NUMBER_AND_LIST, format!(
+                        "Cannot add number and list (line {})",
+                        line
+                    ))),
+                }
+            }
+
+            Instruction::IndexSubtraction{line} => {
+                let arg2 = pop(&mut stack);
+                match (pop(&mut stack), arg2) {
+                    (Value::Number(n1), Value::Number(n2)) => Value::Number(n1 - n2),
+                    (Value::List(l), Value::Number(n)) => l.index(n)?,
+                    _ => return Err(RuntimeError::MismatchedTypes(codes::SUBTRACT_OR_INDEX_WITH_LIST, format!(
+                        "Cannot subtract or index with list (line {})",
+                        line
+                    ))),
+                }
+            }
+
+            Instruction::Induction(f) => Value::List(inductionlist::induction(
+                Function::Code(program.clone(), *f),
+                pop(&mut stack),
+            )),
+
+            Instruction::Map{function: f, line} => match pop(&mut stack) {
+                Value::List(l) => Value::List(maplist::map(Function::Code(program.clone(), *f), l)),
+                _ => return Err(RuntimeError::MismatchedTypes(codes::MAP_NUMBER, format!(
+                    "Attempt to map number on line {}",
+                    line
+                ))),
+            },
+
+            Instruction::Debug{line, col} => {
+                let eval = pop(&mut stack);
+                observer::debug(*line, *col, &eval);
+                eval
+            }
+
+            Instruction::LoadShared{slot, skip} => {
+                if let Some(Some(eval)) = shared.get(*slot) {
+                    stats::count_shared_reuse();
+                    stack.push(eval.clone());
+                    pc += skip;
+                }
+                continue;
+            }
+
+            Instruction::StoreShared(slot) => {
+                if shared.len() <= *slot {
+                    shared.resize(*slot + 1, None);
+                }
+                shared[*slot] = stack.last().cloned();
+                continue;
+            }
+        };
+        stats::count_node();
+        stack.push(result);
+    }
+    Ok(pop(&mut stack))
+}
+
+// the compiler always pushes the arguments of an instruction before it
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("vm stack underflow")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::parsetree::{parse, ParseTree};
+
+    fn run_tree(pt: &ParseTree, input: &Value) -> String {
+        match evaluate(pt, input).and_then(|v| { v.force_resolve()?; Ok(v) }) {
+            Ok(v) => format!("{}", v),
+            Err(e) => format!("{} {}", e.code(), e),
+        }
+    }
+
+    fn run_vm(pt: &ParseTree, input: &Value) -> String {
+        let program = Rc::new(compile(pt));
+        match run(&program, 0, input).and_then(|v| { v.force_resolve()?; Ok(v) }) {
+            Ok(v) => format!("{}", v),
+            Err(e) => format!("{} {}", e.code(), e),
+        }
+    }
+
+    #[test]
+    fn simple_programs() {
+        for expr in ["1(2)(6)", "1(2)[5]", "2[](3[])(4[](5[])[])[2][0]", "2(()(1)][5]", "2[](3[])(5[])[()(()))"] {
+            let pt = parse(expr).expect("parse error");
+            assert_eq!(run_vm(&pt, &Value::Number(0)), run_tree(&pt, &Value::Number(0)));
+        }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        for expr in ["0[0)", "0[][()([]))[0]", "5[][1]", "0(()]"] {
+            let pt = parse(expr).expect("parse error");
+            let output = run_vm(&pt, &Value::Number(0));
+            assert!(output.starts_with("LM"));
+            assert_eq!(output, run_tree(&pt, &Value::Number(0)));
+        }
+    }
+
+    // every example should behave the same on both backends
+    #[test]
+    fn differential_examples() {
+        crate::differential::behaves_the_same(&[], |pt| pt.clone(), true);
+    }
+}